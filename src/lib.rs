@@ -0,0 +1,2 @@
+// The linear version is also built as a library so that its doctests (which check what doesn't compile) get run
+pub mod llnormal;
@@ -1,5 +1,5 @@
 mod backend;
 mod textui;
-mod core;
+pub mod core;
 
 pub use textui::game_loop;
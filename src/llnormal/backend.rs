@@ -1,5 +1,7 @@
-use super::core::{GameCore, GameCard, is_set, GameMaker};
-pub use super::core::{Number, Shape, Shading, Colour, Card};
+use std::collections::HashSet;
+
+use super::core::{GameCore, GameCard, check_set, is_set, third_card};
+pub use super::core::{Number, Shape, Shading, Colour, Card, GameResult};
 
 pub struct Move(pub usize, pub usize, pub usize);
 
@@ -9,8 +11,8 @@ pub struct Game<'a> {
 }
 
 impl<'a> Game<'a> {
-    pub fn get_score(&self) -> i32 {
-        self.core.get_score()
+    pub(crate) fn current_score(&self) -> i32 {
+        self.core.current_score()
     }
 
     pub fn remaining_cards(&self) -> usize {
@@ -28,10 +30,10 @@ impl<'a> Game<'a> {
         }
     }
 
-    pub fn attempt_move(&mut self, Move(c1, c2, c3): Move) -> Result<(), PlayError> {
+    pub fn attempt_move(&mut self, mve: Move) -> Result<(), PlayError> {
 
         // Sort the card indices
-        let mut v = vec![c1, c2, c3];
+        let mut v = vec![mve.0, mve.1, mve.2];
         v.sort();
         let [c1, c2, c3]: [usize; 3] = v.try_into().expect("Impossible to not have 3 values");
         
@@ -39,88 +41,59 @@ impl<'a> Game<'a> {
             return Err(PlayError::InvalidMove);
         }
 
+        let new_max = self.in_play.len() - 3;
+
         let card3 = self.in_play.remove(c3);
         let card2 = self.in_play.remove(c2);
         let card1 = self.in_play.remove(c1);
 
+        // Inserting in increasing order of position puts every card back exactly where it was
         let return_cards = |v: &mut Vec<GameCard<'a>>, cards: [GameCard<'a>; 3]| {
             cards.into_iter()
                 .zip([c1, c2, c3])
                 .for_each(|(c, i)| {
-                    v.insert(i.min(v.len()-1), c);
+                    v.insert(i, c);
                 });
         };
 
+        let set = match check_set([card1, card2, card3]) {
+            Ok(set) => set,
+            Err(cards) => {
+                let cards = self.core.penalise(cards);
+                return_cards(&mut self.in_play, cards);
+                return Err(PlayError::NotASet);
+            }
+        };
 
-
-        match self.core.make_move([card1, card2, card3]) {
-            Ok(None) => { // TODO: proper card moving with > 12 cards
-                let a = [self.in_play.pop().unwrap(), self.in_play.pop().unwrap(), self.in_play.pop().unwrap()];
-                return_cards(&mut self.in_play, a);
+        match self.core.make_move(set) {
+            None if self.core.remaining_cards() == 0 => {
+                // No more cards in deck. The cards are already out of play and that's it
                 Ok(())
-            },        
-            Ok(Some(cards)) => {
-                return_cards(&mut self.in_play, cards);
+            }
+            None => {
+                // Too many cards. Do not replace
+
+                // Fill the gaps before the new end of the play area with cards from the end (minimize card
+                // movements). Cards from the set past the new end are already gone, so the cards left past it are
+                // exactly the ones that need to move
+                let mut moved: Vec<(usize, GameCard<'a>)> = [mve.0, mve.1, mve.2].into_iter()
+                    .filter(|&i| i < new_max)
+                    .map(|i| (i, self.in_play.pop().expect("Play area cannot be empty")))
+                    .collect();
+                moved.sort_by_key(|&(i, _)| i);
+                for (i, c) in moved {
+                    self.in_play.insert(i, c);
+                }
+
+                assert_eq!(self.in_play.len() % 3, 0);
                 Ok(())
             }
-            Err(cards) => {
+            Some(cards) => {
+                // Normal play. Replace cards in-place
                 return_cards(&mut self.in_play, cards);
-                Err(PlayError::NotASet)
+                Ok(())
             }
         }
-        // if self.deck.len() == 0 {
-        //     // No more cards in deck. Remove cards from play and that's it
-        //     let mut v = vec![c1, c2, c3];
-        //     v.sort();
-        //     v.iter().rev().for_each(|&i| {
-        //         self.in_play.remove(i as usize);
-        //     });
-        // } else if self.in_play.len() <= 12 {
-        //     assert_eq!(self.in_play.len(), 12);
-        //     // Normal play. Replace cards in-place
-        //     vec![c1, c2, c3].iter().for_each(|&i| {
-        //         self.in_play[i] = self.deck.pop().expect("Impossible to fail to draw a card: deck always has multiple of 3 cards");
-        //     });
-        
-        // } else {
-        //     // Too many cards. Do not replace
-
-        //     // Remove cards and replace them with cards from the end (minimize card movements)
-        //     // Here's how this is going to work:
-        //     // 1. figure out where the end of the play area that's still going to exist is (current len - 3)
-        //     // 2. remove all cards that are part of a set past that point
-        //     // 3. Swap out the cards before the new end with those past the new end
-
-        //     let new_max = self.in_play.len() - 3;
-
-        //     let mut pre = vec![];
-        //     let mut post = vec![];
-
-        //     for i in [c1, c2, c3] {
-        //         if i < new_max {
-        //             pre.push(i);
-        //         } else {
-        //             post.push(i);
-        //         }
-        //     }
-
-        //     // Sort post from largest to smallest (so that removals don't change positions of other cards to be removed)
-        //     post.sort();
-        //     post.reverse();
-
-        //     for i in post {
-        //         self.in_play.remove(i);
-        //     }
-
-        //     // Replace cards before the break with cards from the end
-        //     for i in pre {
-        //         self.in_play[i] = self.in_play.pop().expect("Deck cannot be empty");
-        //     }
-
-        //     assert_eq!(self.in_play.len() % 3, 0);
-        // }
-
-        // Ok(self)
     }
 
     pub fn draw_3(&mut self) -> Result<(), ()> {
@@ -150,6 +123,20 @@ impl<'a> Game<'a> {
 
         sets
     }
+
+    pub fn has_set(&self) -> bool {
+        has_set(&self.in_play.iter().map(|c| *c.get_card()).collect::<Vec<Card>>())
+    }
+
+    // The game ends when the deck is empty and there are no sets left to find
+    pub fn is_over(&self) -> bool {
+        self.remaining_cards() == 0 && !self.has_set()
+    }
+
+    // Hands every card still in play back to the core, which is the only way to get the final score
+    pub fn finish(self) -> GameResult {
+        self.core.finish(self.in_play)
+    }
 }
 
 pub enum PlayError {
@@ -160,8 +147,19 @@ pub enum PlayError {
 
 
 
-pub fn has_set(_cards: &[Card]) -> bool {
-    panic!("Not implemented!");
+// Checks every pair for its third card rather than checking every triple, so this is O(n^2) instead of O(n^3)
+pub fn has_set(cards: &[Card]) -> bool {
+    let on_board: HashSet<&Card> = cards.iter().collect();
+
+    for i in 0..cards.len() {
+        for j in (i+1)..cards.len() {
+            if on_board.contains(&third_card(&cards[i], &cards[j])) {
+                return true;
+            }
+        }
+    }
+
+    false
 }
 
 #[cfg(test)]
@@ -169,9 +167,143 @@ mod tests {
  
     use super::{Number::*, Shape::*, Shading::*, Colour::*};
     use super::*;
+    use super::super::core::GameMaker;
     
     #[test]
-    fn test() {
- 
+    fn has_set_small_boards() {
+        assert!(!has_set(&[]));
+        assert!(!has_set(&[Card::new(One, Oval, Empty, Red), Card::new(Two, Oval, Empty, Red)]));
+        assert!(has_set(&[Card::new(One, Oval, Empty, Red), Card::new(Two, Wave, Full, Green), Card::new(Two, Oval, Empty, Red), Card::new(Three, Oval, Empty, Red)]));
+        assert!(!has_set(&[Card::new(One, Oval, Empty, Red), Card::new(Two, Wave, Full, Green), Card::new(Two, Oval, Empty, Red), Card::new(Three, Oval, Empty, Green)]));
+    }
+
+    #[test]
+    fn has_set_agrees_with_find_sets() {
+        for seed in 0..50 {
+            GameMaker.with_core_seeded(seed, |a| {
+                let game = Game::new(a);
+                assert_eq!(game.has_set(), !game.find_sets().is_empty());
+                game.finish();
+            });
+        }
+    }
+
+    fn board(game: &Game) -> Vec<Card> {
+        game.cards_in_play().iter().map(|c| *c.get_card()).collect()
+    }
+
+    #[test]
+    fn sets_are_replaced_in_place() {
+        for seed in 0..20 {
+            GameMaker.with_core_seeded(seed, |a| {
+                let mut game = Game::new(a);
+                // Extra cards wouldn't be replaced
+                if !game.has_set() {
+                    game.finish();
+                    return;
+                }
+                let before = board(&game);
+                let Move(c1, c2, c3) = game.find_sets().remove(0);
+                let chosen = [c1, c2, c3];
+                let remaining = game.remaining_cards();
+
+                assert!(game.attempt_move(Move(c3, c1, c2)).is_ok());
+                let after = board(&game);
+                assert_eq!(game.remaining_cards(), remaining - 3);
+                assert_eq!(after.len(), before.len());
+                for i in 0..before.len() {
+                    assert_eq!(after[i] == before[i], !chosen.contains(&i));
+                }
+                game.finish();
+            });
+        }
+    }
+
+    #[test]
+    fn failed_moves_keep_the_board() {
+        GameMaker.with_core_seeded(3, |a| {
+            let mut game = Game::new(a);
+            let before = board(&game);
+            let sets = game.find_sets();
+            let (i, j, k) = (0..12).flat_map(|i| (i+1..12).flat_map(move |j| (j+1..12).map(move |k| (i, j, k))))
+                .find(|&(i, j, k)| !sets.iter().any(|m| (m.0, m.1, m.2) == (i, j, k)))
+                .unwrap();
+            assert!(matches!(game.attempt_move(Move(k, i, j)), Err(PlayError::NotASet)));
+            assert!(matches!(game.attempt_move(Move(0, 0, 1)), Err(PlayError::InvalidMove)));
+            assert!(matches!(game.attempt_move(Move(0, 1, 12)), Err(PlayError::InvalidMove)));
+            assert_eq!(board(&game), before);
+            assert_eq!(game.finish().score, -1);
+        });
+    }
+
+    #[test]
+    fn extra_cards_fill_the_gaps() {
+        let mut checked = 0;
+        for seed in 0..100 {
+            GameMaker.with_core_seeded(seed, |a| {
+                let mut game = Game::new(a);
+                game.draw_3().unwrap();
+                game.draw_3().unwrap();
+                let before = board(&game);
+                let new_max = before.len() - 3;
+                for Move(c1, c2, c3) in game.find_sets() {
+                    let chosen = [c1, c2, c3];
+                    // Only interesting when a card past the new end has to move
+                    if chosen.iter().all(|&i| i >= new_max) {
+                        continue;
+                    }
+                    let remaining = game.remaining_cards();
+                    assert!(game.attempt_move(Move(c1, c2, c3)).is_ok());
+                    let after = board(&game);
+                    assert_eq!(game.remaining_cards(), remaining);
+                    assert_eq!(after.len(), new_max);
+                    for i in 0..new_max {
+                        if !chosen.contains(&i) {
+                            assert_eq!(after[i], before[i]);
+                        }
+                    }
+                    let mut left: Vec<Card> = before.iter().enumerate().filter(|(i, _)| !chosen.contains(i)).map(|(_, &c)| c).collect();
+                    let mut after = after;
+                    left.sort_by_key(|c| c.to_int());
+                    after.sort_by_key(|c| c.to_int());
+                    assert_eq!(after, left);
+                    checked += 1;
+                    break;
+                }
+                game.finish();
+            });
+        }
+        assert!(checked > 0);
+    }
+
+    #[test]
+    fn cards_run_out() {
+        for seed in 0..10 {
+            GameMaker.with_core_seeded(seed, |a| {
+                let mut game = Game::new(a);
+                // A point for each set taken and one off for each draw
+                let mut score = 0;
+                loop {
+                    if game.remaining_cards() == 0 && game.has_set() {
+                        let before = board(&game);
+                        let Move(c1, c2, c3) = game.find_sets().remove(0);
+                        assert!(game.attempt_move(Move(c2, c3, c1)).is_ok());
+                        let expected: Vec<Card> = before.iter().enumerate().filter(|&(i, _)| i != c1 && i != c2 && i != c3).map(|(_, &c)| c).collect();
+                        assert_eq!(board(&game), expected);
+                        score += 1;
+                    } else if let Some(Move(c1, c2, c3)) = game.find_sets().pop() {
+                        assert!(game.attempt_move(Move(c1, c2, c3)).is_ok());
+                        score += 1;
+                    } else if game.draw_3().is_ok() {
+                        score -= 1;
+                    } else {
+                        break;
+                    }
+                }
+                assert!(game.is_over());
+                let leftover = board(&game);
+                assert_eq!(game.finish(), GameResult { score, leftover });
+            });
+        }
     }
  }
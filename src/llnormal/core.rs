@@ -1,38 +1,43 @@
+use std::collections::HashSet;
 use std::marker::PhantomData;
+use std::mem;
+#[cfg(debug_assertions)]
+use std::thread;
 use derivative::Derivative;
 
-use rand::thread_rng;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Number {
     One,
     Two,
     Three,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Shape {
     Oval,
     Wave,
     Diamond,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Shading {
     Empty,
     Half,
     Full,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum Colour {
     Red,
     Green,
     Purple,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Card {
     number: Number,
     shape: Shape,
@@ -54,6 +59,18 @@ impl<'a> GameCard<'a> {
     }
 }
 
+// Every card has to end up played in a set or handed back to `GameCore::finish`. Those forget the cards, so any
+// card that actually gets dropped has been lost somewhere. Only checked in debug builds
+#[cfg(debug_assertions)]
+impl<'a> Drop for GameCard<'a> {
+    fn drop(&mut self) {
+        // Don't turn a panic into an abort
+        if !thread::panicking() {
+            panic!("{:?} was dropped without being played or handed back to the game", self.card);
+        }
+    }
+}
+
 
 impl Card {
     pub fn new(number: Number, shape: Shape, shading: Shading, colour: Colour) -> Self {
@@ -96,14 +113,84 @@ impl Card {
             Colour::Purple => 2,
         })
     }
+
+    pub fn from_int(i: u8) -> Self {
+        assert!(i < 81, "Card numbers must be less than 81");
+        Card {
+            number: [Number::One, Number::Two, Number::Three][(i / 27) as usize],
+            shape: [Shape::Oval, Shape::Wave, Shape::Diamond][(i / 9 % 3) as usize],
+            shading: [Shading::Empty, Shading::Half, Shading::Full][(i / 3 % 3) as usize],
+            colour: [Colour::Red, Colour::Green, Colour::Purple][(i % 3) as usize],
+        }
+    }
+}
+
+// Returns the card that would complete a set with the other two cards
+pub fn third_card(c1: &Card, c2: &Card) -> Card {
+    // Each attribute is a base 3 digit of the card number, and the digits of a set always sum to a multiple of 3
+    let (mut a, mut b) = (c1.to_int(), c2.to_int());
+    let mut out = 0;
+    let mut place = 1;
+    for _ in 0..4 {
+        out += (6 - a % 3 - b % 3) % 3 * place;
+        a /= 3;
+        b /= 3;
+        place *= 3;
+    }
+    Card::from_int(out)
 }
 
 pub struct GameMaker;
 
 impl GameMaker {
+    /// Deals a new game and hands it to `func`. The game's cards can only ever be used with the core they came from,
+    /// and can't be kept after `func` returns.
+    ///
+    /// Cards from one game can't be played in another:
+    ///
+    /// ```compile_fail
+    /// use set::llnormal::core::{GameMaker, check_set};
+    ///
+    /// GameMaker.with_core(|(_core1, cards1)| {
+    ///     GameMaker.with_core(|(mut core2, _cards2)| {
+    ///         let [c1, c2, c3, ..] = cards1;
+    ///         let _ = check_set([c1, c2, c3]).map(|set| core2.make_move(set));
+    ///     })
+    /// })
+    /// ```
+    ///
+    /// ```compile_fail
+    /// use set::llnormal::core::{GameMaker, check_set};
+    ///
+    /// GameMaker.with_core(|(mut core1, _cards1)| {
+    ///     GameMaker.with_core(|(_core2, cards2)| {
+    ///         let [c1, c2, c3, ..] = cards2;
+    ///         let _ = check_set([c1, c2, c3]).map(|set| core1.make_move(set));
+    ///     })
+    /// })
+    /// ```
+    ///
+    /// Cards can't get out of the closure:
+    ///
+    /// ```compile_fail
+    /// use set::llnormal::core::GameMaker;
+    ///
+    /// let mut escaped = vec![];
+    /// GameMaker.with_core(|(_core, cards)| {
+    ///     escaped.extend(cards);
+    /// });
+    /// ```
     pub fn with_core<'a, F: for <'any> FnOnce((GameCore<'any>, [GameCard<'any>; 12]))>(&self, func: F) {
+        self.with_core_rng(&mut thread_rng(), func)
+    }
+
+    pub fn with_core_seeded<'a, F: for <'any> FnOnce((GameCore<'any>, [GameCard<'any>; 12]))>(&self, seed: u64, func: F) {
+        self.with_core_rng(&mut StdRng::seed_from_u64(seed), func)
+    }
+
+    pub fn with_core_rng<'a, R: Rng + ?Sized, F: for <'any> FnOnce((GameCore<'any>, [GameCard<'any>; 12]))>(&self, rng: &mut R, func: F) {
         func(
-            GameCore::new(make_shuffled_deck())
+            GameCore::new(make_shuffled_deck(rng))
         )
     }
 
@@ -114,11 +201,19 @@ impl GameMaker {
     }
 }
 
+// How a game went, once all its cards are accounted for
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GameResult {
+    pub score: i32,
+    pub leftover: Vec<Card>,
+}
+
 #[derive(Derivative)]
 #[derivative(Debug)]
 pub struct GameCore<'a> {
     deck: Vec<Card>,
-    extant_cards: usize,
+    // Every card that has been dealt and not played yet
+    extant: HashSet<Card>,
     score: i32,
     #[derivative(Debug="ignore")]
     mark_invariant: PhantomData<&'a mut &'a ()>,
@@ -128,7 +223,7 @@ impl<'a> GameCore<'a> {
     fn new(deck: Vec<Card>) -> (Self, [GameCard<'a>; 12]) {
         let mut game = GameCore {
             deck: vec![],
-            extant_cards: 12,
+            extant: HashSet::new(),
             score: 0,
             mark_invariant: PhantomData,
         };
@@ -144,6 +239,7 @@ impl<'a> GameCore<'a> {
             .expect("Cannot fail to get 12 cards from freshly made deck");
 
         game.deck = iter.collect();
+        game.extant = cards.iter().map(|c| c.card).collect();
 
         (game, cards)
     }
@@ -152,7 +248,8 @@ impl<'a> GameCore<'a> {
         self.deck.len()
     }
 
-    pub fn get_score(&self) -> i32 {
+    // The score so far, for showing during the game. The final score only comes from `finish`
+    pub(crate) fn current_score(&self) -> i32 {
         self.score
     }
 
@@ -167,7 +264,7 @@ impl<'a> GameCore<'a> {
     }
 
     fn draw_3_internal(&mut self) -> Option<[GameCard<'a>; 3]> {
-        let out = (0..3).map(|_| {
+        let out: Option<[GameCard<'a>; 3]> = (0..3).map(|_| {
             match self.deck.pop() {
                 Some(c) => Some(GameCard{card: c, phantom: PhantomData}),
                 None => None,
@@ -175,26 +272,76 @@ impl<'a> GameCore<'a> {
         }).collect::<Option<Vec<GameCard<'a>>>>()
             .map(|v| {v.try_into().expect("Must have taken 3 cards by construction")});
 
-        if out.is_some() {
-            self.extant_cards += 3;
+        if let Some(cards) = &out {
+            self.extant.extend(cards.iter().map(|c| c.card));
         }
 
         out
     }
 
-    pub fn make_move(&mut self, cards: [GameCard<'a>; 3]) -> Result<Option<[GameCard<'a>; 3]>, [GameCard<'a>; 3]> {
-        
-        if is_set(&cards[0].card, &cards[1].card, &cards[2].card) {
-            self.score += 1;
-            self.extant_cards -= 3;
-            if self.extant_cards < 12 {
-                Ok(self.draw_3_internal())
-            } else {
-                Ok(None)
-            }
+    /// Takes a set out of the game, replacing it if there are too few cards left in play.
+    ///
+    /// ```
+    /// use set::llnormal::core::{GameMaker, check_set};
+    ///
+    /// GameMaker.with_core_seeded(0, |(mut core, cards)| {
+    ///     let [c1, c2, c3, rest @ ..] = cards;
+    ///     let mut in_play = Vec::from(rest);
+    ///     let expected = match check_set([c1, c2, c3]) {
+    ///         Ok(set) => {
+    ///             in_play.extend(core.make_move(set).unwrap());
+    ///             1
+    ///         }
+    ///         Err(cards) => {
+    ///             in_play.extend(core.penalise(cards));
+    ///             -1
+    ///         }
+    ///     };
+    ///     let result = core.finish(in_play);
+    ///     assert_eq!(result.score, expected);
+    ///     assert_eq!(result.leftover.len(), 12);
+    /// });
+    /// ```
+    pub fn make_move(&mut self, set: VerifiedSet<'a>) -> Option<[GameCard<'a>; 3]> {
+        let VerifiedSet(cards) = set;
+        for c in cards {
+            self.extant.remove(&c.card);
+            mem::forget(c);
+        }
+        self.score += 1;
+        if self.extant.len() < 12 {
+            self.draw_3_internal()
         } else {
-            self.score -= 1;
-            Err(cards)
+            None
+        }
+    }
+
+    // Costs a point for calling something that isn't a set. The cards stay in play, so they're handed back
+    pub fn penalise(&mut self, cards: [GameCard<'a>; 3]) -> [GameCard<'a>; 3] {
+        self.score -= 1;
+        cards
+    }
+
+    /// Ends the game. Every card that hasn't been played has to be handed back.
+    ///
+    /// ```should_panic
+    /// use set::llnormal::core::GameMaker;
+    ///
+    /// GameMaker.with_core(|(core, cards)| {
+    ///     let [_lost, rest @ ..] = cards;
+    ///     core.finish(Vec::from(rest));
+    /// });
+    /// ```
+    pub fn finish(self, remaining: Vec<GameCard<'a>>) -> GameResult {
+        assert_eq!(remaining.len(), self.extant.len(), "Cards have gone missing from the game");
+        let leftover: Vec<Card> = remaining.iter().map(|c| c.card).collect();
+        // With the counts equal, this also rules out any card being handed back twice
+        assert!(leftover.iter().copied().collect::<HashSet<Card>>() == self.extant,
+                "The cards handed back aren't the ones still in the game");
+        remaining.into_iter().for_each(mem::forget);
+        GameResult {
+            score: self.score,
+            leftover,
         }
     }
 
@@ -217,11 +364,11 @@ fn make_unshuffled_deck() -> Vec<Card> {
     deck
 }
 
-fn make_shuffled_deck() -> Vec<Card> {
+fn make_shuffled_deck<R: Rng + ?Sized>(rng: &mut R) -> Vec<Card> {
     let mut deck = make_unshuffled_deck();
 
     // Shuffle the deck
-    deck.shuffle(&mut thread_rng());
+    deck.shuffle(rng);
 
     deck
 }
@@ -240,6 +387,65 @@ pub fn is_set(card1: &Card, card2: &Card, card3: &Card) -> bool {
     )
 }
 
+/// Proof that three cards from the same game form a set. The only way to get one is from `check_set`.
+///
+/// ```compile_fail
+/// use set::llnormal::core::{GameMaker, VerifiedSet};
+///
+/// GameMaker.with_core(|(mut core, cards)| {
+///     let [c1, c2, c3, ..] = cards;
+///     core.make_move(VerifiedSet([c1, c2, c3]));
+/// });
+/// ```
+///
+/// Each card can only be used once:
+///
+/// ```compile_fail
+/// use set::llnormal::core::{GameMaker, check_set};
+///
+/// GameMaker.with_core(|(mut core, cards)| {
+///     let [c1, c2, c3, ..] = cards;
+///     let _ = check_set([c1, c2, c3]).map(|set| core.make_move(set));
+///     let _ = check_set([c1, c2, c3]).map(|set| core.make_move(set));
+/// });
+/// ```
+///
+/// and can't be copied:
+///
+/// ```compile_fail
+/// use set::llnormal::core::{GameMaker, check_set};
+///
+/// GameMaker.with_core(|(mut core, cards)| {
+///     let [c1, c2, ..] = cards;
+///     let _ = check_set([c1.clone(), c1, c2]).map(|set| core.make_move(set));
+/// });
+/// ```
+///
+/// A set can't be made from the cards of two different games:
+///
+/// ```compile_fail
+/// use set::llnormal::core::{GameMaker, check_set};
+///
+/// GameMaker.with_core(|(_core1, cards1)| {
+///     GameMaker.with_core(|(_core2, cards2)| {
+///         let [c1, c2, ..] = cards1;
+///         let [c3, ..] = cards2;
+///         let _ = check_set([c1, c2, c3]);
+///     })
+/// })
+/// ```
+#[derive(Debug)]
+pub struct VerifiedSet<'a>([GameCard<'a>; 3]);
+
+// Hands the cards back if they aren't a set
+pub fn check_set<'a>(cards: [GameCard<'a>; 3]) -> Result<VerifiedSet<'a>, [GameCard<'a>; 3]> {
+    if is_set(&cards[0].card, &cards[1].card, &cards[2].card) {
+        Ok(VerifiedSet(cards))
+    } else {
+        Err(cards)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{*, Number::*, Shape::*, Colour::*, Shading::*};
@@ -255,13 +461,36 @@ mod tests {
 
     #[test]
     fn test_make_shuffled_deck() {
-        let deck = make_shuffled_deck();
+        let deck = make_shuffled_deck(&mut thread_rng());
         assert_eq!(deck.len(), 81);
         assert_eq!(deck.iter().filter(|&&c| c == Card::new(One, Oval, Half, Purple)).count(), 1);
         assert_eq!(deck.iter().filter(|&&c| c == Card::new(Three, Wave, Full, Green)).count(), 1);
         assert_eq!(deck.iter().filter(|&&c| c == Card::new(Two, Diamond, Empty, Red)).count(), 1);
     }
 
+    #[test]
+    fn test_from_int() {
+        for c in make_unshuffled_deck() {
+            assert_eq!(Card::from_int(c.to_int()), c);
+        }
+    }
+
+    #[test]
+    fn third_card_test() {
+        let deck = make_unshuffled_deck();
+        for c1 in &deck {
+            for c2 in &deck {
+                if c1 == c2 {
+                    continue;
+                }
+                let target_c3 = third_card(c1, c2);
+                for c3 in &deck {
+                    assert_eq!(c3 == &target_c3, is_set(c1, c2, c3))
+                }
+            }
+        }
+    }
+
     #[test]
     fn is_set_diff_num() {
         assert!(is_set(&Card::new(One, Oval, Empty, Red), &Card::new(Two, Oval, Empty, Red), &Card::new(Three, Oval, Empty, Red)));
@@ -298,19 +527,110 @@ mod tests {
 	assert!(!is_set(&Card::new(One, Oval, Half, Green), &Card::new(Two, Wave, Empty, Purple), &Card::new(Three, Wave, Full, Red)));
     }
 
+    // Plays the last three cards, putting back whatever is left in play
+    fn play_last_three<'a>(core: &mut GameCore<'a>, cards: &mut Vec<GameCard<'a>>) {
+        let three = [cards.pop().unwrap(), cards.pop().unwrap(), cards.pop().unwrap()];
+        match check_set(three) {
+            Ok(set) => cards.extend(core.make_move(set).into_iter().flatten()),
+            Err(three) => cards.extend(core.penalise(three)),
+        }
+    }
+
+    #[test]
+    fn test_with_core_seeded_is_reproducible() {
+        let mut first = vec![];
+        GameMaker.with_core_seeded(7, |(core, cards)| {
+            first = cards.iter().map(|c| *c.get_card()).collect();
+            core.finish(cards.into());
+        });
+        let mut second = vec![];
+        GameMaker.with_core_seeded(7, |(core, cards)| {
+            second = cards.iter().map(|c| *c.get_card()).collect();
+            core.finish(cards.into());
+        });
+        assert_eq!(first.len(), 12);
+        assert_eq!(first, second);
+    }
+
+    #[test]
+    fn test_check_set() {
+        GameMaker.with_unshuffled_core(|(mut core, cards)| {
+            // The first three cards of the unshuffled deck differ only in colour, so they're a set
+            let [c1, c2, c3, c4, c5, c6, c7, rest @ ..] = cards;
+            let set = check_set([c1, c2, c3]).unwrap();
+            let mut in_play: Vec<GameCard> = core.make_move(set).unwrap().into();
+
+            let not_a_set = check_set([c4, c5, c7]).unwrap_err();
+            in_play.extend(core.penalise(not_a_set));
+
+            in_play.push(c6);
+            in_play.extend(rest);
+            let result = core.finish(in_play);
+            assert_eq!(result.score, 0);
+            assert_eq!(result.leftover.len(), 12);
+        });
+    }
+
+    #[test]
+    fn test_finish() {
+        GameMaker.with_core_seeded(3, |(mut core, cards)| {
+            let mut cards = Vec::from(cards);
+            let board: Vec<Card> = cards.iter().map(|c| *c.get_card()).collect();
+            cards.extend(core.draw_3().unwrap());
+            let result = core.finish(cards);
+            assert_eq!(result.score, -1);
+            assert_eq!(result.leftover.len(), 15);
+            assert_eq!(result.leftover[..12], board[..]);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "Cards have gone missing")]
+    fn test_finish_with_missing_cards() {
+        GameMaker.with_core_seeded(3, |(core, cards)| {
+            let mut cards = Vec::from(cards);
+            let missing = cards.pop().unwrap();
+            mem::forget(missing);
+            core.finish(cards);
+        });
+    }
+
+    #[test]
+    #[should_panic(expected = "aren't the ones still in the game")]
+    fn test_finish_with_swapped_cards() {
+        GameMaker.with_core_seeded(3, |(mut core, cards)| {
+            let mut cards = Vec::from(cards);
+            mem::forget(cards.pop().unwrap());
+            // A card from the deck, which was never dealt
+            let undealt = core.deck.pop().unwrap();
+            cards.push(GameCard { card: undealt, phantom: PhantomData });
+            core.finish(cards);
+        });
+    }
+
+    #[cfg(debug_assertions)]
+    #[test]
+    #[should_panic(expected = "was dropped without being played")]
+    fn test_dropped_cards_are_caught() {
+        GameMaker.with_core_seeded(3, |(_core, cards)| {
+            let [dropped, ..] = cards;
+            drop(dropped);
+        });
+    }
+
     #[test]
     fn test_lifetime_isolation_1() {
         let game_maker1 = GameMaker;
-        game_maker1.with_core(|(mut core1, mut cards1)| {
+        game_maker1.with_core(|(mut core1, cards1)| {
             let game_maker2 = GameMaker;
-            game_maker2.with_core(|(mut core2, mut cards2)| {
+            game_maker2.with_core(|(mut core2, cards2)| {
                 let mut cards1 = Vec::from(cards1);
-                core1.make_move([cards1.pop().unwrap(), cards1.pop().unwrap(), cards1.pop().unwrap()]);
+                play_last_three(&mut core1, &mut cards1);
                 let mut cards2 = Vec::from(cards2);
-                core2.make_move([cards2.pop().unwrap(), cards2.pop().unwrap(), cards2.pop().unwrap()]);
-                // SHOULD NOT COMPILE WITH EITHER OF THE FOLLOWING LINES
-                //core1.make_move([cards2.pop().unwrap(), cards2.pop().unwrap(), cards2.pop().unwrap()]);
-                //core2.make_move([cards1.pop().unwrap(), cards1.pop().unwrap(), cards1.pop().unwrap()]);
+                play_last_three(&mut core2, &mut cards2);
+                // Playing cards in the other game is checked not to compile by the doctests on GameMaker::with_core
+                core1.finish(cards1);
+                core2.finish(cards2);
             })
         })
     }
@@ -319,15 +639,15 @@ mod tests {
     fn test_lifetime_isolation_2() {
         let game_maker1 = GameMaker;
         let game_maker2 = GameMaker;
-        game_maker1.with_core(|(mut core1, mut cards1)| {
-            game_maker2.with_core(|(mut core2, mut cards2)| {
+        game_maker1.with_core(|(mut core1, cards1)| {
+            game_maker2.with_core(|(mut core2, cards2)| {
                 let mut cards1 = Vec::from(cards1);
-                core1.make_move([cards1.pop().unwrap(), cards1.pop().unwrap(), cards1.pop().unwrap()]);
+                play_last_three(&mut core1, &mut cards1);
                 let mut cards2 = Vec::from(cards2);
-                core2.make_move([cards2.pop().unwrap(), cards2.pop().unwrap(), cards2.pop().unwrap()]);
-                // SHOULD NOT COMPILE WITH EITHER OF THE FOLLOWING LINES
-                //core1.make_move([cards2.pop().unwrap(), cards2.pop().unwrap(), cards2.pop().unwrap()]);
-                //core2.make_move([cards1.pop().unwrap(), cards1.pop().unwrap(), cards1.pop().unwrap()]);
+                play_last_three(&mut core2, &mut cards2);
+                // Playing cards in the other game is checked not to compile by the doctests on GameMaker::with_core
+                core1.finish(cards1);
+                core2.finish(cards2);
             })
         })
     }
@@ -335,15 +655,15 @@ mod tests {
     #[test]
     fn test_lifetime_isolation_3() {
         let game_maker1 = GameMaker;
-        game_maker1.with_core(|(mut core1, mut cards1)| {
-            game_maker1.with_core(|(mut core2, mut cards2)| {
+        game_maker1.with_core(|(mut core1, cards1)| {
+            game_maker1.with_core(|(mut core2, cards2)| {
                 let mut cards1 = Vec::from(cards1);
-                core1.make_move([cards1.pop().unwrap(), cards1.pop().unwrap(), cards1.pop().unwrap()]);
+                play_last_three(&mut core1, &mut cards1);
                 let mut cards2 = Vec::from(cards2);
-                core2.make_move([cards2.pop().unwrap(), cards2.pop().unwrap(), cards2.pop().unwrap()]);
-                // SHOULD NOT COMPILE WITH EITHER OF THE FOLLOWING LINES
-                //core1.make_move([cards2.pop().unwrap(), cards2.pop().unwrap(), cards2.pop().unwrap()]);
-                //core2.make_move([cards1.pop().unwrap(), cards1.pop().unwrap(), cards1.pop().unwrap()]);
+                play_last_three(&mut core2, &mut cards2);
+                // Playing cards in the other game is checked not to compile by the doctests on GameMaker::with_core
+                core1.finish(cards1);
+                core2.finish(cards2);
             })
         })
     }
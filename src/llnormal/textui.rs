@@ -1,8 +1,9 @@
-use super::{backend::{Card, Number, Shape, Shading, Colour as CardColour, Game, Move, PlayError}, core::{GameCore, GameCard, GameMaker}};
+use super::{backend::{Card, Number, Shape, Shading, Colour as CardColour, Game, GameResult, Move, PlayError}, core::{GameCore, GameCard, GameMaker}};
 
 use std::io;
 use std::io::Write;
 
+use rand::{thread_rng, Rng};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 fn get_line() -> String {
@@ -15,7 +16,7 @@ fn get_line() -> String {
     }
 }
 
-pub fn game_loop() {
+pub fn game_loop(mut seed: Option<u64>) {
     print!("Welcome to set (implemented with linear logic)! Would you like to play a game? (y/n): ");
     io::stdout().flush().unwrap();
     'outer: loop {
@@ -36,7 +37,8 @@ pub fn game_loop() {
             }
         }
 
-        play_game();
+        // Only the first game uses a seed given on the command line
+        play_game(seed.take().unwrap_or_else(|| thread_rng().gen()));
 
         print!("That was fun! Would you like to play again? (y/n): ");
         io::stdout().flush().unwrap();
@@ -45,35 +47,22 @@ pub fn game_loop() {
 
 enum Choice {
     Draw3,
+    Hint,
+    Quit,
     Cheat,
     Move(usize, usize, usize),
 }
 
+// The keys for the cards on screen, column by column (see the README)
+const LETTERS: [char; 21] = ['q', 'a', 'z', 'w', 's', 'x', 'e', 'd', 'c', 'r', 'f', 'v', 't', 'g', 'b', 'y', 'h', 'n', 'u', 'j', 'm'];
+
 fn letter_to_index(letter: char) -> Result<usize, ()> {
-    match letter.to_lowercase().next().unwrap() {
-        'q' => Ok(0),
-        'a' => Ok(1),
-        'z' => Ok(2),
-        'w' => Ok(3),
-        's' => Ok(4),
-        'x' => Ok(5),
-        'e' => Ok(6),
-        'd' => Ok(7),
-        'c' => Ok(8),
-        'r' => Ok(9),
-        'f' => Ok(10),
-        'v' => Ok(11),
-        't' => Ok(12),
-        'g' => Ok(13),
-        'b' => Ok(14),
-        'y' => Ok(15),
-        'h' => Ok(16),
-        'n' => Ok(17),
-        'u' => Ok(18),
-        'j' => Ok(19),
-        'm' => Ok(20),
-        _ => Err(()),
-    }
+    let letter = letter.to_lowercase().next().unwrap();
+    LETTERS.iter().position(|&l| l == letter).ok_or(())
+}
+
+fn index_to_letter(index: usize) -> Option<char> {
+    LETTERS.get(index).copied()
 }
 
 fn get_move() -> Choice {
@@ -81,6 +70,8 @@ fn get_move() -> Choice {
         let ans = get_line();
         match ans.to_ascii_lowercase().get(0..1) {
             Some("p") => break Choice::Draw3,
+            Some(";") => break Choice::Hint,
+            Some("k") => break Choice::Quit,
             Some("l") => break Choice::Cheat,
             _ => (),
         }
@@ -99,19 +90,27 @@ fn get_move() -> Choice {
 
 
 
-fn play_game() {
-    GameMaker.with_core(|a| {play_game_cont(a)});
+fn play_game(seed: u64) {
+    println!("Seed: {}", seed);
+    GameMaker.with_core_seeded(seed, |a| {play_game_cont(a)});
 }
 
 fn play_game_cont<'a>(a: (GameCore<'a>, [GameCard<'a>; 12])) {
     let mut game = Game::new(a);
+    // Each hint gives away a bit more of the same set, until the board changes
+    let mut hint_level = 0;
+    let mut hinted_board: Vec<Card> = vec![];
     // Main game loop
     'outer: loop {
         print_game(&game);
+        if game.is_over() {
+            print_summary(&game.finish());
+            return;
+        }
         match get_move() {
             Choice::Draw3 => {
-                if game.cards_in_play().len() == 21 {
-                    println!("Can't have more than 21 cards in play! (hint: there's guaranteed to be a set here)");
+                if game.has_set() {
+                    println!("There's still a set on the board! Keep looking.");
                     continue;
                 }
                 match game.draw_3() {
@@ -120,13 +119,19 @@ fn play_game_cont<'a>(a: (GameCore<'a>, [GameCard<'a>; 12])) {
                     }
                     Err(()) => {
                         println!("There aren't enough cards in the deck. Game over!");
-                        break 'outer;
+                        println!("Final score: {}", game.finish().score);
+                        return;
                     }
                 }
             }
+            Choice::Quit => {
+                println!("Thanks for playing!");
+                game.finish();
+                return;
+            }
             Choice::Cheat => {
-                let sets = &game.find_sets();
-                if sets.len() == 0 {
+                let sets = game.find_sets();
+                if sets.is_empty() {
                     println!("There are no sets on the board. You have to draw 3 cards.");
                 } else {
                     if sets.len() == 1 {
@@ -135,9 +140,33 @@ fn play_game_cont<'a>(a: (GameCore<'a>, [GameCard<'a>; 12])) {
                         println!("There are {} sets on the board:", sets.len());
                     }
                     for s in sets.iter() {
-                        println!("{} {} {}", card_string(&game.cards_in_play()[s.0].get_card()), card_string(&game.cards_in_play()[s.1].get_card()), card_string(&game.cards_in_play()[s.2].get_card()));
+                        println!("{} {} {}", card_string(game.cards_in_play()[s.0].get_card()), card_string(game.cards_in_play()[s.1].get_card()), card_string(game.cards_in_play()[s.2].get_card()));
+                    }
+                }
+                continue 'outer;
+            }
+            Choice::Hint => {
+                let board: Vec<Card> = game.cards_in_play().iter().map(|c| *c.get_card()).collect();
+                if board != hinted_board {
+                    hint_level = 0;
+                    hinted_board = board;
+                }
+                let sets = game.find_sets();
+                let describe = |i: usize| {
+                    let card = card_string(game.cards_in_play()[i].get_card());
+                    match index_to_letter(i) {
+                        Some(letter) => format!("{} ({})", card, letter),
+                        None => card,
                     }
+                };
+                match (sets.first(), hint_level) {
+                    (None, _) => println!("There are no sets on the board. You have to draw 3 cards."),
+                    (Some(_), 0) if sets.len() == 1 => println!("There is 1 set on the board."),
+                    (Some(_), 0) => println!("There are {} sets on the board.", sets.len()),
+                    (Some(s), 1) => println!("{} is part of a set.", describe(s.0)),
+                    (Some(s), _) => println!("{} and {} are part of a set.", describe(s.0), describe(s.1)),
                 }
+                hint_level += 1;
                 continue 'outer;
             }
             Choice::Move(c1, c2, c3) => {
@@ -155,6 +184,16 @@ fn play_game_cont<'a>(a: (GameCore<'a>, [GameCard<'a>; 12])) {
     }
 }
 
+fn print_summary(result: &GameResult) {
+    println!("The deck is empty and there are no sets left. Game over!");
+    println!("Final score: {}", result.score);
+    if result.leftover.is_empty() {
+        println!("You cleared the whole board!");
+    } else {
+        println!("{} cards left over: {}", result.leftover.len(), result.leftover.iter().map(card_string).collect::<Vec<String>>().join(" "));
+    }
+}
+
 const DIAMOND_EMPTY: [&str; 11] =
 [r"          ",
  r"    /\    ",
@@ -275,7 +314,7 @@ const WAVE_FULL: [&str; 11] =
 
 
 fn print_game<'a>(g: &Game<'a>) {
-    println!("Score: {}", g.get_score());
+    println!("Score: {}", g.current_score());
     println!("Cards left in deck: {}", g.remaining_cards());
     println!();
 
@@ -1,16 +1,128 @@
 use std::env;
 
 mod normal;
-mod llnormal;
 mod projective;
 
+use set::llnormal;
+
+// Returns the value following `name` in the argument list, if there is one
+fn option_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|a| a == name)
+        .and_then(|i| args.get(i + 1))
+        .map(|s| s.as_str())
+}
+
 fn main() {
     let args: Vec<String> = env::args().collect();
-    if args.contains(&"--projective".to_string()) {
-        projective::game_loop();
+
+    let seed = match option_value(&args, "--seed").map(str::parse::<u64>) {
+        None => None,
+        Some(Ok(seed)) => Some(seed),
+        Some(Err(_)) => {
+            eprintln!("--seed must be followed by a non-negative integer");
+            return;
+        }
+    };
+
+    let hint_costs = match option_value(&args, "--hint-costs").map(|costs| costs.split(',').map(|c| c.trim().parse::<i32>()).collect::<Result<Vec<i32>, _>>()) {
+        None => normal::HintCosts::default(),
+        Some(Ok(costs)) if costs.len() == 3 => normal::HintCosts {
+            count: costs[0],
+            one_card: costs[1],
+            two_cards: costs[2],
+        },
+        Some(_) => {
+            eprintln!("--hint-costs must be followed by three comma separated integers, e.g. 0,1,1");
+            return;
+        }
+    };
+
+    if args.get(1).map(String::as_str) == Some("analyze") {
+        analyze(&args, seed);
+    } else if args.get(1).map(String::as_str) == Some("bench") {
+        let iterations = match option_value(&args, "--samples").map(str::parse::<usize>) {
+            None => 10000,
+            Some(Ok(iterations)) => iterations,
+            Some(Err(_)) => {
+                eprintln!("--samples must be followed by a non-negative integer");
+                return;
+            }
+        };
+        for benchmark in normal::run_benchmarks(iterations, seed.unwrap_or(0)) {
+            println!("{}", benchmark);
+        }
+    } else if let Some(file) = option_value(&args, "--replay") {
+        normal::replay_loop(file);
+    } else if args.contains(&"--puzzle".to_string()) {
+        let date = match option_value(&args, "--date").map(str::parse::<normal::Date>) {
+            None => None,
+            Some(Ok(date)) => Some(date),
+            Some(Err(e)) => {
+                eprintln!("{}", e);
+                return;
+            }
+        };
+        let sets = match option_value(&args, "--sets").map(str::parse::<usize>) {
+            None => normal::DEFAULT_PUZZLE_SETS,
+            Some(Ok(sets)) => sets,
+            Some(Err(_)) => {
+                eprintln!("--sets must be followed by a non-negative integer");
+                return;
+            }
+        };
+        normal::puzzle_loop(date, sets);
+    } else if args.contains(&"--projective".to_string()) {
+        projective::game_loop(seed);
     } else if args.contains(&"--linear".to_string()) {
-        llnormal::game_loop();
+        llnormal::game_loop(seed);
     } else {
-        normal::game_loop();
+        normal::game_loop(normal::GameOptions {
+            seed,
+            load: option_value(&args, "--load").map(String::from),
+            record: option_value(&args, "--record").map(String::from),
+            players: option_value(&args, "--players")
+                .map(|names| names.split(',').map(|n| n.trim().to_string()).filter(|n| !n.is_empty()).collect())
+                .unwrap_or_default(),
+            rules: if args.contains(&"--official".to_string()) {
+                normal::Rules::Official
+            } else {
+                normal::Rules::Casual
+            },
+            hint_costs,
+        });
+    }
+}
+
+// set analyze <caps|games|max-caps> [--samples N] [--seed N] [--threads N] [--strategy first|random|keep] [--format table|csv|json]
+fn analyze(args: &[String], seed: Option<u64>) {
+    let parsed = (|| -> Result<(normal::Analysis, usize, usize, normal::Format), String> {
+        let mut analysis = args.get(2).ok_or("analyze needs to be told what to analyze: caps, games or max-caps")?
+            .parse::<normal::Analysis>().map_err(|e| e.to_string())?;
+        if let (normal::Analysis::Games(_), Some(strategy)) = (analysis, option_value(args, "--strategy")) {
+            analysis = normal::Analysis::Games(strategy.parse::<normal::StrategyKind>().map_err(|e| e.to_string())?);
+        }
+        let samples = match option_value(args, "--samples").map(str::parse::<usize>) {
+            None => 10000,
+            Some(Ok(samples)) if samples > 0 => samples,
+            Some(_) => return Err("--samples must be followed by a positive integer".to_string()),
+        };
+        let threads = match option_value(args, "--threads").map(str::parse::<usize>) {
+            None => normal::default_threads(),
+            Some(Ok(threads)) if threads > 0 => threads,
+            Some(_) => return Err("--threads must be followed by a positive integer".to_string()),
+        };
+        let format = option_value(args, "--format").map_or(Ok(normal::Format::Table), str::parse::<normal::Format>).map_err(|e| e.to_string())?;
+        Ok((analysis, samples, threads, format))
+    })();
+
+    match parsed {
+        Ok((analysis, samples, threads, format)) => {
+            let seed = seed.unwrap_or_else(rand::random);
+            let report = normal::run_analysis(analysis, samples, seed, threads, |done, total| eprint!("\r{}/{}", done, total));
+            eprintln!();
+            print!("{}", report.render(format));
+        }
+        Err(e) => eprintln!("{}", e),
     }
 }
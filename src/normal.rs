@@ -1,7 +1,15 @@
 mod backend;
 mod textui;
 mod analysis;
+mod replay;
+mod puzzle;
+mod generator;
+mod symmetry;
+mod report;
 
-pub use textui::game_loop;
-pub use analysis::{print_num_cards_to_forced_set, print_num_cards_to_forced_set_multithreaded};
+pub use textui::{game_loop, puzzle_loop, replay_loop, GameOptions};
+pub use puzzle::{Date, DEFAULT_PUZZLE_SETS};
+pub use backend::{HintCosts, Rules};
+pub use report::{Analysis, Format, StrategyKind, run_analysis};
+pub use analysis::{default_threads, run_benchmarks};
 
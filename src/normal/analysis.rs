@@ -1,64 +1,264 @@
-use std::{collections::{HashSet, HashMap}, thread::{spawn, JoinHandle}, sync::mpsc::sync_channel, ops::Deref};
+use std::{collections::{HashSet, HashMap}, fmt, hint::black_box, thread, time::{Duration, Instant}, sync::mpsc::channel, sync::atomic::{AtomicUsize, Ordering}};
 
-use super::backend::{Game, make_shuffled_deck, make_deck, Card, third_card};
+use rand::{Rng, SeedableRng};
+use rand::rngs::StdRng;
 
-const NUM_THREADS: usize = 16;
+use super::backend::{Game, Move, find_sets, find_sets_in, make_shuffled_deck_with_rng, Card, CardMask, third_card, third_index};
+use super::symmetry::{canonical_mask, canonicalise};
 
-pub fn print_num_cards_to_forced_set(count: usize) {
-    let map = (0..count).map(|_| {
-        let v = most_cards_with_no_set();
-       // println!("{}", v);
-        v})
-        .fold([0; 21], |mut m, v| {
-            m[v] += 1;
-            m
-        });
+// Samples are handed out to threads in chunks, each with its own seed, so the results only depend on the seed and not
+// on how many threads there are or which thread ran what
+const CHUNK_SIZE: usize = 256;
+
+// How many times each value came up
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Histogram {
+    counts: Vec<usize>,
+}
+
+impl Histogram {
+    pub fn new() -> Self {
+        Histogram::default()
+    }
+
+    pub fn add(&mut self, value: usize) {
+        if self.counts.len() <= value {
+            self.counts.resize(value + 1, 0);
+        }
+        self.counts[value] += 1;
+    }
+
+    pub fn merge(&mut self, other: &Histogram) {
+        if self.counts.len() < other.counts.len() {
+            self.counts.resize(other.counts.len(), 0);
+        }
+        for (count, other) in self.counts.iter_mut().zip(&other.counts) {
+            *count += other;
+        }
+    }
+
+    pub fn get(&self, value: usize) -> usize {
+        self.counts.get(value).copied().unwrap_or(0)
+    }
 
-    for (num, occurences) in map.iter().enumerate() {
-        if occurences != &0 {
-            println!("{}: {} ({}%)", num, occurences, 100.*(*occurences as f64)/(count as f64));
+    // The count for every value from 0 up to the largest one seen
+    pub fn counts(&self) -> &[usize] {
+        let len = self.counts.iter().rposition(|&c| c != 0).map_or(0, |i| i + 1);
+        &self.counts[..len]
+    }
+
+    pub fn total(&self) -> usize {
+        self.counts.iter().sum()
+    }
+
+    pub fn max_value(&self) -> Option<usize> {
+        self.counts().len().checked_sub(1)
+    }
+
+    // None if nothing has been added
+    pub fn mean(&self) -> Option<f64> {
+        if self.total() == 0 {
+            return None;
         }
+        let sum: usize = self.counts.iter().enumerate().map(|(v, c)| v * c).sum();
+        Some(sum as f64 / self.total() as f64)
     }
 }
 
-pub fn print_num_cards_to_forced_set_multithreaded(count: usize) {
-    // thread to keep track of the counts
-    let mut arr = [0; 21];
-    let (sender, receiver) = sync_channel(100);
-    let _ = spawn(move || {
-        loop {
-            match receiver.recv() {
-                Err(_) => break,
-                Ok(v) => arr[v] += 1
+impl fmt::Display for Histogram {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (num, occurences) in self.counts().iter().enumerate() {
+            if occurences != &0 {
+                writeln!(f, "{}: {} ({}%)", num, occurences, 100.*(*occurences as f64)/(self.total() as f64))?;
             }
         }
-    });
-    println!("Starting threads");
-    let handles: Vec<JoinHandle<()>> = (0..NUM_THREADS).map(|_| {
-        let sender = sender.clone();
-        spawn(move || {
-            (0..(count/NUM_THREADS)).for_each(|_| {
-                sender.send(most_cards_with_no_set()).unwrap();
+        Ok(())
+    }
+}
+
+// The finalising step of SplitMix64, which scrambles the bits so that nearby inputs give unrelated outputs
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
+// The seed for one chunk of a run. Mixing the run's seed first means chunk k of seed s isn't chunk k + 1 of seed s + 1
+fn chunk_seed(seed: u64, chunk: usize) -> u64 {
+    mix(mix(seed).wrapping_add((chunk as u64).wrapping_mul(0x9e3779b97f4a7c15)))
+}
+
+pub fn default_threads() -> usize {
+    thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+// Runs `sample` `count` times spread over `threads` threads, and counts up the results. `progress` is called on this
+// thread with the number of samples done so far (and the total) as they come in
+pub fn run_parallel<F, P>(count: usize, threads: usize, seed: u64, sample: F, progress: P) -> Histogram
+where
+    F: Fn(&mut StdRng) -> usize + Sync,
+    P: FnMut(usize, usize),
+{
+    run_chunks(count, threads, seed, |rng, histogram: &mut Histogram| histogram.add(sample(rng)), |total, histogram| total.merge(&histogram), progress)
+}
+
+// Like run_parallel, but each sample adds to a T, and the Ts from each chunk are merged together at the end
+fn run_chunks<T, F, M, P>(count: usize, threads: usize, seed: u64, sample: F, merge: M, mut progress: P) -> T
+where
+    T: Default + Send,
+    F: Fn(&mut StdRng, &mut T) + Sync,
+    M: Fn(&mut T, T),
+    P: FnMut(usize, usize),
+{
+    let chunks = count.div_ceil(CHUNK_SIZE);
+    let next_chunk = AtomicUsize::new(0);
+    let (sender, receiver) = channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, chunks.max(1)) {
+            let sender = sender.clone();
+            let (next_chunk, sample) = (&next_chunk, &sample);
+            scope.spawn(move || loop {
+                let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+                if chunk >= chunks {
+                    break;
+                }
+                let mut rng = StdRng::seed_from_u64(chunk_seed(seed, chunk));
+                let mut result = T::default();
+                // The last chunk picks up whatever is left over
+                let samples = count.min((chunk + 1) * CHUNK_SIZE) - chunk * CHUNK_SIZE;
+                for _ in 0..samples {
+                    sample(&mut rng, &mut result);
+                }
+                if sender.send((chunk, samples, result)).is_err() {
+                    break;
+                }
             });
-        })
-    }).collect();
-    
-    for handle in handles {
-        handle.join().unwrap();
+        }
+        // Otherwise the receiver would wait forever for this one
+        drop(sender);
+
+        // Chunks are merged in order, so that even merges that care about order give the same answer every time
+        let mut waiting: Vec<Option<T>> = (0..chunks).map(|_| None).collect();
+        let (mut merged, mut done) = (0, 0);
+        let mut total = T::default();
+        for (chunk, samples, result) in receiver {
+            waiting[chunk] = Some(result);
+            while let Some(result) = waiting.get_mut(merged).and_then(Option::take) {
+                merge(&mut total, result);
+                merged += 1;
+            }
+            done += samples;
+            progress(done, count);
+        }
+        total
+    })
+}
+
+// Picks which set to take when there is more than one on the board
+pub trait Strategy: Sync {
+    // `sets` is never empty
+    fn choose(&self, game: &Game, sets: &[Move], rng: &mut StdRng) -> Move;
+}
+
+// Always takes the first set found
+pub struct FirstSet;
+
+impl Strategy for FirstSet {
+    fn choose(&self, _game: &Game, sets: &[Move], _rng: &mut StdRng) -> Move {
+        sets[0]
+    }
+}
+
+// Takes any of the sets, all equally likely
+pub struct RandomSet;
+
+impl Strategy for RandomSet {
+    fn choose(&self, _game: &Game, sets: &[Move], rng: &mut StdRng) -> Move {
+        sets[rng.gen_range(0..sets.len())]
+    }
+}
+
+// Takes the set that leaves the most sets behind on the board (before any new cards are dealt)
+pub struct KeepSets;
+
+impl Strategy for KeepSets {
+    fn choose(&self, game: &Game, sets: &[Move], _rng: &mut StdRng) -> Move {
+        let board = game.cards_in_play();
+        let left = |&Move(c1, c2, c3): &Move| {
+            let rest: Vec<Card> = board.iter().enumerate().filter(|&(i, _)| i != c1 && i != c2 && i != c3).map(|(_, &c)| c).collect();
+            find_sets_in(&rest).len()
+        };
+        // max_by_key keeps the last of equal sets, so go backwards to prefer the first
+        *sets.iter().rev().max_by_key(|&m| left(m)).unwrap()
+    }
+}
+
+impl<F: Fn(&Game, &[Move], &mut StdRng) -> Move + Sync> Strategy for F {
+    fn choose(&self, game: &Game, sets: &[Move], rng: &mut StdRng) -> Move {
+        self(game, sets, rng)
+    }
+}
+
+// What happened over a number of complete games
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GameStats {
+    pub games: usize,
+    // How many sets there were on each 12 card board
+    pub sets_on_board: Histogram,
+    // How many times each game had to draw 3 more cards because there was no set
+    pub forced_draws: Histogram,
+    // How many cards were left with no set at the end of each game
+    pub cards_left: Histogram,
+}
+
+impl GameStats {
+    pub fn merge(&mut self, other: &GameStats) {
+        self.games += other.games;
+        self.sets_on_board.merge(&other.sets_on_board);
+        self.forced_draws.merge(&other.forced_draws);
+        self.cards_left.merge(&other.cards_left);
     }
 
-    for (num, occurences) in arr.iter().enumerate() {
-        if occurences != &0 {
-            println!("{}: {} ({}%)", num, occurences, 100.*(*occurences as f64)/(count as f64));
+    // Chance that a 12 card board has no set on it (None if no games were played)
+    pub fn no_set_probability(&self) -> Option<f64> {
+        let boards = self.sets_on_board.total();
+        (boards > 0).then(|| self.sets_on_board.get(0) as f64 / boards as f64)
+    }
+}
+
+// Plays one game to the end, taking sets as soon as there are any and only drawing when there are none
+pub fn simulate_game<S: Strategy + ?Sized>(strategy: &S, rng: &mut StdRng, stats: &mut GameStats) {
+    let mut game = Game::with_rng(StdRng::seed_from_u64(rng.gen()));
+    let mut draws = 0;
+    loop {
+        let sets = find_sets(&game);
+        if game.cards_in_play().len() == 12 {
+            stats.sets_on_board.add(sets.len());
+        }
+        if sets.is_empty() {
+            if game.draw_3().is_err() {
+                break;
+            }
+            draws += 1;
+        } else {
+            let mve = strategy.choose(&game, &sets, rng);
+            game.attempt_move(&mve).expect("Strategies have to pick one of the sets they are given");
         }
     }
+    stats.games += 1;
+    stats.forced_draws.add(draws);
+    stats.cards_left.add(game.cards_in_play().len());
+}
+
+// Plays `count` games over `threads` threads (see run_parallel)
+pub fn simulate_games<S: Strategy + ?Sized, P: FnMut(usize, usize)>(count: usize, threads: usize, seed: u64, strategy: &S, progress: P) -> GameStats {
+    run_chunks(count, threads, seed, |rng, stats| simulate_game(strategy, rng, stats), |total, stats| total.merge(&stats), progress)
 }
-    
-        
 
-pub fn most_cards_with_no_set() -> usize {
+pub fn most_cards_with_no_set_with_rng<R: Rng + ?Sized>(rng: &mut R) -> usize {
     let mut cards: HashSet<Card> = HashSet::new();
-    for c in make_shuffled_deck() {
+    for c in make_shuffled_deck_with_rng(rng) {
         let mut bad_card = false;
         for c2 in cards.iter() {
             if cards.contains(&third_card(&c, c2)) {
@@ -74,20 +274,396 @@ pub fn most_cards_with_no_set() -> usize {
     cards.len()
 }
 
-pub fn most_cards_with_no_set_fast() -> usize {
-    let mut cards: HashSet<Card> = HashSet::new();
-    for c in make_shuffled_deck() {
-        let mut bad_card = false;
-        for c2 in cards.iter() {
-            if cards.contains(&third_card(&c, c2)) {
-                bad_card = true;
-                break;
-            }
-        }
-        if bad_card {
+// Same as most_cards_with_no_set, but keeps track of the cards that would make a set instead of looking for them
+pub fn most_cards_with_no_set_fast_with_rng<R: Rng + ?Sized>(rng: &mut R) -> usize {
+    let mut cards: Vec<usize> = Vec::with_capacity(20);
+    let mut blocked: CardMask = 0;
+    for c in make_shuffled_deck_with_rng(rng) {
+        let c = c.to_index();
+        if blocked & (1 << c) != 0 {
             continue;
         }
-        cards.insert(c);
+        for &c2 in &cards {
+            blocked |= 1 << third_index(c, c2);
+        }
+        cards.push(c);
     }
     cards.len()
 }
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Benchmark {
+    pub name: &'static str,
+    pub iterations: usize,
+    pub total: Duration,
+}
+
+impl Benchmark {
+    pub fn per_iteration(&self) -> Duration {
+        self.total.div_f64(self.iterations.max(1) as f64)
+    }
+}
+
+impl fmt::Display for Benchmark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} iterations in {:.3?} ({:.3?} each)", self.name, self.iterations, self.total, self.per_iteration())
+    }
+}
+
+fn time<F: FnMut()>(name: &'static str, iterations: usize, mut f: F) -> Benchmark {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        f();
+    }
+    Benchmark { name, iterations, total: start.elapsed() }
+}
+
+// Times the slow and fast ways of finding how many cards it takes to force a set, and finding the sets on random boards
+pub fn run_benchmarks(iterations: usize, seed: u64) -> Vec<Benchmark> {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let slow = time("most_cards_with_no_set", iterations, || {
+        black_box(most_cards_with_no_set_with_rng(&mut rng));
+    });
+    let mut rng = StdRng::seed_from_u64(seed);
+    let fast = time("most_cards_with_no_set_fast", iterations, || {
+        black_box(most_cards_with_no_set_fast_with_rng(&mut rng));
+    });
+    // Dealing the boards isn't part of what's being timed
+    let mut rng = StdRng::seed_from_u64(seed);
+    let games: Vec<Game> = (0..iterations).map(|_| Game::with_rng(&mut rng)).collect();
+    let mut games = games.iter();
+    let find = time("find_sets", iterations, || {
+        black_box(find_sets(games.next().unwrap()));
+    });
+    vec![slow, fast, find]
+}
+
+// Exact search for sets of cards with no set in them ("caps").
+//
+// Up to symmetry, the hyperplane that meets a cap in the most cards (say m of them) is the one where the last attribute
+// is 0, which is the first 27 cards. If m is at least 5 those cards aren't all in a plane (a plane only has room for 4),
+// so we can also assume the cap contains 0, e1, e2 and e3, and then e4 as well if it has cards outside that hyperplane.
+// The search then decides for each remaining card in turn whether it is in the cap, and gives up on a branch once some
+// hyperplane has more than m cards, or there isn't room for enough cards in the hyperplanes of some direction
+const MAX_CAP_IN_HYPERPLANE: usize = 9;
+const BASIS: [usize; 5] = [0, 1, 3, 9, 27];
+const FIRST_HYPERPLANE: CardMask = (1 << 27) - 1;
+
+fn dot(a: usize, b: usize) -> usize {
+    (a % 3 * (b % 3) + a / 3 % 3 * (b / 3 % 3) + a / 9 % 3 * (b / 9 % 3) + a / 27 * (b / 27)) % 3
+}
+
+// Each way of splitting the cards into three parallel hyperplanes
+fn hyperplanes() -> Vec<[CardMask; 3]> {
+    (1..81usize)
+        // a and 2a give the same hyperplanes, so only take the normals whose first nonzero digit is 1
+        .filter(|&normal| (0..4).map(|d| normal / 3usize.pow(d) % 3).find(|&digit| digit != 0) == Some(1))
+        .map(|normal| {
+            let mut parts = [0; 3];
+            for card in 0..81 {
+                parts[dot(normal, card)] |= 1 << card;
+            }
+            parts
+        })
+        .collect()
+}
+
+struct CapSearch<'a> {
+    size: usize,
+    // Most cards in any hyperplane, and exactly how many are in the first one
+    per_hyperplane: usize,
+    hyperplanes: &'a [[CardMask; 3]],
+    find_all: bool,
+    found: Vec<CardMask>,
+}
+
+impl CapSearch<'_> {
+    // Returns true once the search is done
+    fn search(&mut self, cap: CardMask, candidates: CardMask) -> bool {
+        let have = cap.count_ones() as usize;
+        if have == self.size {
+            self.found.push(cap);
+            return !self.find_all;
+        }
+        let possible = cap | candidates;
+        if have + (candidates.count_ones() as usize) < self.size
+            || ((possible & FIRST_HYPERPLANE).count_ones() as usize) < self.per_hyperplane {
+            return false;
+        }
+        for parts in self.hyperplanes {
+            let mut room = 0;
+            for part in parts {
+                if (cap & part).count_ones() as usize > self.per_hyperplane {
+                    return false;
+                }
+                room += ((possible & part).count_ones() as usize).min(self.per_hyperplane);
+            }
+            if room < self.size {
+                return false;
+            }
+        }
+
+        let card = candidates.trailing_zeros() as usize;
+        let mut with_card = candidates & !(1 << card);
+        let mut rest = cap;
+        while rest != 0 {
+            let other = rest.trailing_zeros() as usize;
+            with_card &= !(1 << third_index(card, other));
+            rest &= rest - 1;
+        }
+        self.search(cap | (1 << card), with_card) || self.search(cap, candidates & !(1 << card))
+    }
+}
+
+fn search_caps(size: usize, find_all: bool) -> Vec<CardMask> {
+    let hyperplanes = hyperplanes();
+    let mut found = vec![];
+    // Three parallel hyperplanes hold the whole cap, so the biggest has at least a third of it. Caps that are crowded
+    // into one hyperplane are the easiest to find, so look for those first
+    for per_hyperplane in (size.div_ceil(3)..=size.min(MAX_CAP_IN_HYPERPLANE)).rev() {
+        let mut fixed: Vec<usize> = BASIS[..if per_hyperplane >= 5 { 4 } else { per_hyperplane.min(3) }].to_vec();
+        if size > per_hyperplane {
+            fixed.push(BASIS[4]);
+        }
+
+        let cap: CardMask = fixed.iter().fold(0, |m, &c| m | (1 << c));
+        let mut candidates: CardMask = ((1 << 81) - 1) & !cap;
+        for &i in &fixed {
+            for &j in &fixed {
+                if i != j {
+                    candidates &= !(1 << third_index(i, j));
+                }
+            }
+        }
+
+        let mut search = CapSearch {
+            size,
+            per_hyperplane,
+            hyperplanes: &hyperplanes,
+            find_all,
+            found: vec![],
+        };
+        search.search(cap, candidates);
+        found.extend(search.found);
+        if !find_all && !found.is_empty() {
+            break;
+        }
+    }
+    found
+}
+
+fn mask_to_cards(mask: CardMask) -> Vec<Card> {
+    (0..81).filter(|i| mask & (1 << i) != 0).map(Card::from_index).collect()
+}
+
+// A set of `size` cards with no set in it, or None if there is no such thing
+pub fn find_cap(size: usize) -> Option<Vec<Card>> {
+    search_caps(size, false).first().map(|&cap| mask_to_cards(cap))
+}
+
+// Every set of `size` cards with no set in it, up to symmetry (each one in its canonical form)
+pub fn all_caps(size: usize) -> Vec<Vec<Card>> {
+    let forms: HashSet<CardMask> = search_caps(size, true).iter().map(|&cap| canonical_mask(&mask_to_cards(cap))).collect();
+    let mut forms: Vec<CardMask> = forms.into_iter().collect();
+    forms.sort();
+    forms.into_iter().map(mask_to_cards).collect()
+}
+
+// The most cards there can be without a set
+pub fn max_cap_size() -> usize {
+    let mut size = 0;
+    while find_cap(size + 1).is_some() {
+        size += 1;
+    }
+    size
+}
+
+// How many boards of some size have no set on them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SetFreeCount {
+    pub cards: usize,
+    pub set_free: u128,
+    // Boards of this size that aren't the same up to symmetry
+    pub types: usize,
+    pub total: u128,
+}
+
+impl SetFreeCount {
+    // Chance that a freshly dealt board of this size has no set
+    pub fn probability(&self) -> f64 {
+        self.set_free as f64 / self.total as f64
+    }
+}
+
+fn choose(n: u128, k: u128) -> u128 {
+    (0..k).fold(1, |acc, i| acc * (n - i) / (i + 1))
+}
+
+// Counts the boards with no set for every size up to max_cards, exactly.
+//
+// Rather than looking at every board, this goes through the types of set-free board up to symmetry, one size at a time.
+// Each set-free board of k cards comes from k pairs of a (k-1) card set-free board and a card that can be added to it,
+// so the number of boards of a type is the number of such pairs which make that type, divided by k. And the pairs for
+// a type of (k-1) card board are the same for every board of that type, so they only need working out once
+pub fn count_set_free_boards(max_cards: usize) -> Vec<SetFreeCount> {
+    // The canonical form of each type of set-free board of the current size, and how many boards there are of that type
+    let mut types: HashMap<CardMask, u128> = HashMap::from([(0, 1)]);
+    let mut counts = vec![SetFreeCount { cards: 0, set_free: 1, types: 1, total: 1 }];
+
+    for cards in 1..=max_cards.min(MAX_CAP_IN_HYPERPLANE * 3) {
+        let mut pairs: HashMap<CardMask, u128> = HashMap::new();
+        for (&board, &boards) in &types {
+            // Cards that would make a set with two already there can't be added
+            let mut blocked = board;
+            for i in (0..81).filter(|i| board & (1 << i) != 0) {
+                for j in (i+1..81).filter(|j| board & (1 << j) != 0) {
+                    blocked |= 1 << third_index(i, j);
+                }
+            }
+            for card in (0..81).filter(|c| blocked & (1 << c) == 0) {
+                *pairs.entry(canonicalise(board | (1 << card))).or_insert(0) += boards;
+            }
+        }
+
+        types = pairs.into_iter().map(|(board, pairs)| (board, pairs / cards as u128)).collect();
+        counts.push(SetFreeCount {
+            cards,
+            set_free: types.values().sum(),
+            types: types.len(),
+            total: choose(81, cards as u128),
+        });
+        if types.is_empty() {
+            break;
+        }
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::find_sets_in;
+
+    #[test]
+    fn histogram() {
+        let mut histogram = Histogram::new();
+        for v in [3, 1, 3, 0] {
+            histogram.add(v);
+        }
+        let mut other = Histogram::new();
+        other.add(5);
+        histogram.merge(&other);
+        assert_eq!(histogram.counts(), &[1, 1, 0, 2, 0, 1]);
+        assert_eq!(histogram.total(), 5);
+        assert_eq!(histogram.max_value(), Some(5));
+        assert_eq!(histogram.mean(), Some(12. / 5.));
+        assert_eq!(histogram.to_string(), "0: 1 (20%)\n1: 1 (20%)\n3: 2 (40%)\n5: 1 (20%)\n");
+        assert_eq!(Histogram::new().max_value(), None);
+        assert_eq!(Histogram::new().mean(), None);
+        assert_eq!(Histogram::new().to_string(), "");
+    }
+
+    #[test]
+    fn parallel_runs_are_complete_and_reproducible() {
+        let count = 3 * CHUNK_SIZE + 7;
+        let mut last_progress = 0;
+        let one_thread = run_parallel(count, 1, 16, most_cards_with_no_set_with_rng, |done, total| {
+            assert!(done > last_progress && total == count);
+            last_progress = done;
+        });
+        assert_eq!(last_progress, count);
+        assert_eq!(one_thread.total(), count);
+        assert!(one_thread.max_value().unwrap() <= 20);
+        assert_eq!(run_parallel(count, 5, 16, most_cards_with_no_set_with_rng, |_, _| ()), one_thread);
+        assert_ne!(run_parallel(count, 5, 17, most_cards_with_no_set_with_rng, |_, _| ()), one_thread);
+        assert_eq!(run_parallel(0, 4, 16, |_| 0, |_, _| ()).total(), 0);
+    }
+
+    #[test]
+    fn adjacent_seeds_share_no_chunks() {
+        let run = |seed| {
+            let samples: Vec<u64> = run_chunks(8 * CHUNK_SIZE, 4, seed, |rng, v: &mut Vec<u64>| v.push(rng.gen()), |total, v| total.extend(v), |_, _| ());
+            samples.chunks(CHUNK_SIZE).map(<[u64]>::to_vec).collect::<HashSet<_>>()
+        };
+        for seed in [0, 1, 16, u64::MAX - 1] {
+            assert!(run(seed).is_disjoint(&run(seed.wrapping_add(1))));
+        }
+    }
+
+    #[test]
+    fn simulated_games() {
+        let count = CHUNK_SIZE + 10;
+        let stats = simulate_games(count, 4, 17, &FirstSet, |_, _| ());
+        assert_eq!(stats.games, count);
+        assert_eq!(stats.forced_draws.total(), count);
+        assert_eq!(stats.cards_left.total(), count);
+        // Every game ends with a multiple of 3 cards and no set, which can't be more than 20 cards
+        assert!(stats.cards_left.counts().iter().enumerate().all(|(left, &n)| n == 0 || (left % 3 == 0 && left <= 20)));
+        assert!(stats.sets_on_board.total() >= count);
+        assert!(stats.no_set_probability().is_some_and(|p| p > 0. && p < 0.1));
+        assert_eq!(GameStats::default().no_set_probability(), None);
+
+        assert_eq!(simulate_games(count, 1, 17, &FirstSet, |_, _| ()), stats);
+        let strategy = |_: &Game, sets: &[Move], _: &mut StdRng| sets[sets.len() - 1];
+        assert_eq!(simulate_games(20, 2, 17, &strategy, |_, _| ()).games, 20);
+        assert_eq!(simulate_games(20, 2, 17, &KeepSets, |_, _| ()).games, 20);
+        assert_eq!(simulate_games(20, 2, 17, &RandomSet, |_, _| ()).games, 20);
+    }
+
+    #[test]
+    fn set_free_counts() {
+        let counts = count_set_free_boards(6);
+        let set_free: Vec<u128> = counts.iter().map(|c| c.set_free).collect();
+        // Only three cards can be a set, and there are 1080 sets
+        assert_eq!(set_free[..4], [1, 81, 3240, 85320 - 1080]);
+        // Four cards have at most one set, and five at most two, which share a card (there are 81 * (40 choose 2) such pairs)
+        assert_eq!(set_free[4], choose(81, 4) - 1080 * 78);
+        assert_eq!(set_free[5], choose(81, 5) - 1080 * choose(78, 2) + 81 * choose(40, 2));
+        assert_eq!(counts.iter().map(|c| c.types).collect::<Vec<usize>>(), vec![1, 1, 1, 1, 2, 3, 7]);
+        assert_eq!(counts[4].total, 1663740);
+        assert_eq!(counts[3].probability(), 78. / 79.);
+    }
+
+    #[test]
+    fn fast_cap_size_agrees() {
+        for seed in 0..50 {
+            let slow = most_cards_with_no_set_with_rng(&mut StdRng::seed_from_u64(seed));
+            assert_eq!(most_cards_with_no_set_fast_with_rng(&mut StdRng::seed_from_u64(seed)), slow);
+        }
+    }
+
+    #[test]
+    fn benchmarks_run() {
+        let benchmarks = run_benchmarks(5, 20);
+        assert_eq!(benchmarks.iter().map(|b| b.name).collect::<Vec<&str>>(), vec!["most_cards_with_no_set", "most_cards_with_no_set_fast", "find_sets"]);
+        assert!(benchmarks.iter().all(|b| b.iterations == 5));
+    }
+
+    #[test]
+    fn hyperplanes_split_the_deck() {
+        let planes = hyperplanes();
+        assert_eq!(planes.len(), 40);
+        for parts in planes {
+            assert_eq!(parts.iter().map(|p| p.count_ones()).collect::<Vec<u32>>(), vec![27, 27, 27]);
+            assert_eq!(parts[0] | parts[1] | parts[2], (1 << 81) - 1);
+        }
+    }
+
+    #[test]
+    fn max_cap_is_20() {
+        let cap = find_cap(20).expect("There are 20 cards with no set");
+        assert_eq!(cap.len(), 20);
+        assert!(find_sets_in(&cap).is_empty());
+        assert_eq!(find_cap(21), None);
+    }
+
+    #[test]
+    fn small_caps() {
+        assert_eq!(find_cap(0), Some(vec![]));
+        assert_eq!(all_caps(2).len(), 1);
+        // Three cards that aren't a set are all alike, and four are either in a plane or not
+        assert_eq!(all_caps(3).len(), 1);
+        assert_eq!(all_caps(4).len(), 2);
+        assert!(find_sets_in(&find_cap(9).unwrap()).is_empty());
+    }
+}
@@ -1,4 +1,10 @@
-use rand::thread_rng;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 
 enum AbstractOption {
@@ -196,17 +202,264 @@ impl Card {
     pub fn get_colour(&self) -> Colour {
         self.colour
     }
+
+    // Encodes the card as a number from 0 to 80, with one base 3 digit per attribute
+    pub fn to_index(self) -> usize {
+        (match self.number {
+            Number::One => 0,
+            Number::Two => 27,
+            Number::Three => 2*27,
+        } + match self.shape {
+            Shape::Oval => 0,
+            Shape::Wave => 9,
+            Shape::Diamond => 2*9,
+        } + match self.shading {
+            Shading::Empty => 0,
+            Shading::Half => 3,
+            Shading::Full => 2*3,
+        } + match self.colour {
+            Colour::Red => 0,
+            Colour::Green => 1,
+            Colour::Purple => 2,
+        })
+    }
+
+    pub fn from_index(i: usize) -> Self {
+        assert!(i < 81, "Card indices must be less than 81");
+        Card {
+            number: [Number::One, Number::Two, Number::Three][i / 27],
+            shape: [Shape::Oval, Shape::Wave, Shape::Diamond][i / 9 % 3],
+            shading: [Shading::Empty, Shading::Half, Shading::Full][i / 3 % 3],
+            colour: [Colour::Red, Colour::Green, Colour::Purple][i % 3],
+        }
+    }
+}
+
+// Cards are written as number, shape, shading, colour, e.g. 2WHR for two red half-shaded waves
+impl fmt::Display for Card {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let number = match self.number {
+            Number::One => '1',
+            Number::Two => '2',
+            Number::Three => '3',
+        };
+        let shape = match self.shape {
+            Shape::Oval => 'O',
+            Shape::Wave => 'W',
+            Shape::Diamond => 'D',
+        };
+        let shading = match self.shading {
+            Shading::Empty => 'E',
+            Shading::Half => 'H',
+            Shading::Full => 'F',
+        };
+        let colour = match self.colour {
+            Colour::Red => 'R',
+            Colour::Green => 'G',
+            Colour::Purple => 'P',
+        };
+        write!(f, "{number}{shape}{shading}{colour}")
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseCardError(String);
+
+impl fmt::Display for ParseCardError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid card \"{}\"", self.0)
+    }
+}
+
+impl Error for ParseCardError {}
+
+impl FromStr for Card {
+    type Err = ParseCardError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseCardError(s.to_string());
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() != 4 {
+            return Err(err());
+        }
+        let number = match chars[0] {
+            '1' => Number::One,
+            '2' => Number::Two,
+            '3' => Number::Three,
+            _ => return Err(err()),
+        };
+        let shape = match chars[1] {
+            'O' => Shape::Oval,
+            'W' => Shape::Wave,
+            'D' => Shape::Diamond,
+            _ => return Err(err()),
+        };
+        let shading = match chars[2] {
+            'E' => Shading::Empty,
+            'H' => Shading::Half,
+            'F' => Shading::Full,
+            _ => return Err(err()),
+        };
+        let colour = match chars[3] {
+            'R' => Colour::Red,
+            'G' => Colour::Green,
+            'P' => Colour::Purple,
+            _ => return Err(err()),
+        };
+        Ok(Card::new(number, shape, shading, colour))
+    }
+}
+
+// THIRD_CARD[i][j] is the index of the card that completes a set with the cards with indices i and j
+// (Each attribute is a base 3 digit of the index, and the digits of a set always sum to a multiple of 3)
+static THIRD_CARD: [[u8; 81]; 81] = make_third_card_table();
+
+const fn make_third_card_table() -> [[u8; 81]; 81] {
+    let mut table = [[0; 81]; 81];
+    let mut i = 0;
+    while i < 81 {
+        let mut j = 0;
+        while j < 81 {
+            let (mut a, mut b) = (i, j);
+            let mut out = 0;
+            let mut place = 1;
+            while place < 81 {
+                out += (6 - a % 3 - b % 3) % 3 * place;
+                a /= 3;
+                b /= 3;
+                place *= 3;
+            }
+            table[i][j] = out as u8;
+            j += 1;
+        }
+        i += 1;
+    }
+    table
+}
+
+// Index version of third_card
+pub fn third_index(i: usize, j: usize) -> usize {
+    THIRD_CARD[i][j] as usize
+}
+
+// A set of cards, with bit i set if the card with index i is present
+pub type CardMask = u128;
+
+pub fn card_mask(cards: &[Card]) -> CardMask {
+    cards.iter().fold(0, |mask, c| mask | (1 << c.to_index()))
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Player {
+    name: String,
+    score: i32,
+}
+
+impl Player {
+    pub fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn get_score(&self) -> i32 {
+        self.score
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Rules {
+    // Players draw 3 cards (for a point) whenever they can't find a set
+    #[default]
+    Casual,
+    // 3 cards are dealt for free whenever there is no set on the board, and asking for more cards when there is a set costs a point
+    Official,
+}
+
+// Each hint gives away more of a set than the last
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hint {
+    // How many sets there are on the board
+    Count(usize),
+    // The position of a card that is in a set
+    OneCard(usize),
+    // The positions of two cards in the same set
+    TwoCards(usize, usize),
+}
+
+// Points lost for each level of hint
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct HintCosts {
+    pub count: i32,
+    pub one_card: i32,
+    pub two_cards: i32,
+}
+
+impl Default for HintCosts {
+    fn default() -> Self {
+        HintCosts {
+            count: 0,
+            one_card: 1,
+            two_cards: 1,
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Game {
     deck: Vec<Card>,
     in_play: Vec<Card>,
-    score: i32,
+    // Always at least one player
+    players: Vec<Player>,
+    history: Vec<HistoryEntry>,
+    // Entries taken off the history by undo, most recently undone last
+    undone: Vec<HistoryEntry>,
+    // Games where a move was undone don't count
+    ranked: bool,
+    rules: Rules,
+    // How many times 3 cards have been dealt because there was no set on the board
+    auto_deals: usize,
+    hint_costs: HintCosts,
+    // How many hints have been given for the current board, and which board that was
+    hint_level: usize,
+    hinted_board: Vec<Card>,
+    hints_taken: usize,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub struct Move(pub usize, pub usize, pub usize);
 
+// Something a player did
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Take(Move),
+    Draw3,
+    Hint,
+}
+
+// How far up the hint ladder the players are
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct HintState {
+    level: usize,
+    board: Vec<Card>,
+    taken: usize,
+}
+
+// Everything about an action needed to undo it
+#[derive(Clone, Debug)]
+pub struct HistoryEntry {
+    pub action: Action,
+    // Who did it
+    pub player: usize,
+    // The cards that were taken, if the action was a valid set
+    pub taken: Option<[Card; 3]>,
+    // Cards moved from the deck to the board, in the order they were drawn
+    pub dealt: Vec<Card>,
+    pub score_delta: i32,
+    // How many times 3 extra cards were dealt automatically afterwards
+    pub auto_deals: usize,
+    board_before: Vec<Card>,
+    hints_before: HintState,
+}
+
 impl Game {
     pub fn remaining_cards(&self) -> usize {
         self.deck.len()
@@ -216,30 +469,253 @@ impl Game {
         &self.in_play[..]
     }
 
+    // Total score of all the players (so just the score, in a single player game)
     pub fn get_score(&self) -> i32 {
-        self.score
+        self.players.iter().map(|p| p.score).sum()
+    }
+
+    pub fn players(&self) -> &[Player] {
+        &self.players[..]
+    }
+
+    // Replaces the single default player with named players, all starting from 0
+    pub fn set_players(&mut self, names: Vec<String>) {
+        assert!(!names.is_empty(), "A game needs at least one player");
+        self.players = names.into_iter().map(|name| Player { name, score: 0 }).collect();
+    }
+
+    pub fn history(&self) -> &[HistoryEntry] {
+        &self.history[..]
+    }
+
+    pub fn is_ranked(&self) -> bool {
+        self.ranked
+    }
+
+    // The game is over once the deck is empty and there are no sets left on the board
+    pub fn status(&self) -> GameStatus {
+        if self.remaining_cards() == 0 && find_sets(self).is_empty() {
+            GameStatus::Finished {
+                leftover: self.in_play.clone(),
+                score: self.get_score(),
+            }
+        } else {
+            GameStatus::InProgress
+        }
+    }
+
+    pub fn get_rules(&self) -> Rules {
+        self.rules
+    }
+
+    pub fn auto_deals(&self) -> usize {
+        self.auto_deals
+    }
+
+    pub fn get_hint_costs(&self) -> HintCosts {
+        self.hint_costs
+    }
+
+    pub fn set_hint_costs(&mut self, costs: HintCosts) {
+        self.hint_costs = costs;
+    }
+
+    // Total number of hints that have been paid for
+    pub fn hints_taken(&self) -> usize {
+        self.hints_taken
+    }
+
+    pub fn hint(&mut self) -> Hint {
+        self.hint_as(0)
+    }
+
+    // Gives the next hint for the current board, charging the given player for it. Panics if there is no such player
+    // Once both cards have been given away, asking again just repeats that hint for free
+    pub fn hint_as(&mut self, player: usize) -> Hint {
+        self.record(player, Action::Hint, |g| g.hint_unrecorded(player))
+    }
+
+    fn hint_unrecorded(&mut self, player: usize) -> Hint {
+        if self.in_play != self.hinted_board {
+            self.hint_level = 0;
+            self.hinted_board = self.in_play.clone();
+        }
+
+        let sets = find_sets(self);
+        let (hint, cost) = match (sets.first(), self.hint_level) {
+            (None, 0) | (Some(_), 0) => (Hint::Count(sets.len()), self.hint_costs.count),
+            // Nothing more to say if there are no sets
+            (None, _) => return Hint::Count(0),
+            (Some(&Move(c1, _, _)), 1) => (Hint::OneCard(c1), self.hint_costs.one_card),
+            (Some(&Move(c1, c2, _)), 2) => (Hint::TwoCards(c1, c2), self.hint_costs.two_cards),
+            (Some(&Move(c1, c2, _)), _) => return Hint::TwoCards(c1, c2),
+        };
+
+        self.hint_level += 1;
+        self.hints_taken += 1;
+        self.players[player].score -= cost;
+        hint
+    }
+
+    fn hint_state(&self) -> HintState {
+        HintState {
+            level: self.hint_level,
+            board: self.hinted_board.clone(),
+            taken: self.hints_taken,
+        }
+    }
+
+    fn set_hint_state(&mut self, state: HintState) {
+        self.hint_level = state.level;
+        self.hinted_board = state.board;
+        self.hints_taken = state.taken;
+    }
+
+    // Switching to official rules immediately deals more cards if there is no set on the board
+    pub fn set_rules(&mut self, rules: Rules) {
+        self.rules = rules;
+        self.auto_deal();
+    }
+
+    // Under official rules, keep dealing 3 cards until there is a set on the board (or the deck runs out)
+    fn auto_deal(&mut self) {
+        if self.rules != Rules::Official {
+            return;
+        }
+        while !has_set(&self.in_play) && self.deck.len() >= 3 {
+            for _ in 0..3 {
+                self.in_play.push(self.deck.pop().expect("Checked there are enough cards"));
+            }
+            self.auto_deals += 1;
+        }
+    }
+
+    // Takes back the last action. Any game where this is used becomes unranked
+    pub fn undo(&mut self) -> Result<(), ()> {
+        let entry = self.history.pop().ok_or(())?;
+
+        self.in_play = entry.board_before.clone();
+        self.deck.extend(entry.dealt.iter().rev());
+        self.players[entry.player].score -= entry.score_delta;
+        self.auto_deals -= entry.auto_deals;
+        self.set_hint_state(entry.hints_before.clone());
+        self.ranked = false;
+
+        self.undone.push(entry);
+        Ok(())
+    }
+
+    // Does the last undone action again
+    pub fn redo(&mut self) -> Result<(), ()> {
+        let entry = self.undone.pop().ok_or(())?;
+
+        // Redoing goes through the normal paths, which would otherwise forget everything else that was undone
+        let undone = std::mem::take(&mut self.undone);
+        match entry.action {
+            Action::Take(mve) => {
+                let _ = self.attempt_move_as(entry.player, &mve);
+            }
+            Action::Draw3 => {
+                let _ = self.draw_3_as(entry.player);
+            }
+            Action::Hint => {
+                self.hint_as(entry.player);
+            }
+        }
+        self.undone = undone;
+
+        Ok(())
+    }
+
+    // Runs an action, adding it to the history if it changed anything
+    fn record<R>(&mut self, player: usize, action: Action, f: impl FnOnce(&mut Self) -> R) -> R {
+        let board_before = self.in_play.clone();
+        let deck_before = self.deck.clone();
+        let score_before = self.players[player].score;
+        let auto_deals_before = self.auto_deals;
+        let hints_before = self.hint_state();
+
+        let result = f(self);
+
+        let score_delta = self.players[player].score - score_before;
+        if score_delta != 0 || self.in_play != board_before || self.hints_taken != hints_before.taken {
+            let taken = match action {
+                Action::Take(Move(c1, c2, c3)) if score_delta > 0 => Some([board_before[c1], board_before[c2], board_before[c3]]),
+                _ => None,
+            };
+            self.history.push(HistoryEntry {
+                action,
+                player,
+                taken,
+                dealt: deck_before[self.deck.len()..].iter().rev().copied().collect(),
+                score_delta,
+                auto_deals: self.auto_deals - auto_deals_before,
+                board_before,
+                hints_before,
+            });
+            self.undone.clear();
+        }
+
+        result
     }
 
     // draw 3 new cards for when the user can't find any more cards
-    pub fn draw_3(&mut self) -> Result<(), ()> {
+    pub fn draw_3(&mut self) -> Result<(), DrawError> {
+        self.draw_3_as(0)
+    }
+
+    // Draw 3 cards, charging the point to the given player. Panics if there is no such player
+    pub fn draw_3_as(&mut self, player: usize) -> Result<(), DrawError> {
+        self.record(player, Action::Draw3, |g| g.draw_3_unrecorded(player))
+    }
+
+    fn draw_3_unrecorded(&mut self, player: usize) -> Result<(), DrawError> {
+        if self.rules == Rules::Official && has_set(&self.in_play) {
+            self.players[player].score -= 1;
+            return Err(DrawError::SetOnBoard);
+        }
+
         for _ in 0..3 {
             match self.deck.pop() {
                 Some(c) => self.in_play.push(c),
-                None => return Err(()),
+                None => return Err(DrawError::DeckEmpty),
             }
         }
 
         // Deduct a point from the score
-        self.score -= 1;
+        self.players[player].score -= 1;
 
         Ok(())
     }
 
     pub fn new() -> Self {
+        Self::with_rng(thread_rng())
+    }
+
+    // Deal a game from a deck shuffled by a seeded RNG, so the same seed always gives the same game
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng<R: Rng>(mut rng: R) -> Self {
+        Self::from_deck(make_shuffled_deck_with_rng(&mut rng))
+    }
+
+    // Deal a game from a deck in a known order (cards are drawn from the end of the deck)
+    pub fn from_deck(deck: Vec<Card>) -> Self {
         let mut game = Game {
-            deck: make_shuffled_deck(),
+            deck,
             in_play: vec![],
-            score: 0
+            players: vec![Player { name: "Player 1".to_string(), score: 0 }],
+            history: vec![],
+            undone: vec![],
+            ranked: true,
+            rules: Rules::Casual,
+            auto_deals: 0,
+            hint_costs: HintCosts::default(),
+            hint_level: 0,
+            hinted_board: vec![],
+            hints_taken: 0,
         };
 
         // Draw until we have 12 cards
@@ -250,7 +726,16 @@ impl Game {
         game
     }
 
-    pub fn attempt_move(&mut self, &Move(c1, c2, c3): &Move) -> Result<(), PlayError> {
+    pub fn attempt_move(&mut self, mve: &Move) -> Result<(), PlayError> {
+        self.attempt_move_as(0, mve)
+    }
+
+    // Attempt a move on behalf of the given player, who gets the point (or loses it). Panics if there is no such player
+    pub fn attempt_move_as(&mut self, player: usize, mve: &Move) -> Result<(), PlayError> {
+        self.record(player, Action::Take(*mve), |g| g.attempt_move_unrecorded(player, mve))
+    }
+
+    fn attempt_move_unrecorded(&mut self, player: usize, &Move(c1, c2, c3): &Move) -> Result<(), PlayError> {
         if (c1 == c2) || (c2 == c3) || (c1 == c3) || (c1 >= self.in_play.len()) || (c2 >= self.in_play.len()) || (c3 >= self.in_play.len()) {
             return Err(PlayError::InvalidMove);
         }
@@ -260,11 +745,11 @@ impl Game {
         let card3 = self.in_play[c3];
 
         if !is_set(&card1, &card2, &card3) {
-            self.score -= 1;
+            self.players[player].score -= 1;
             return Err(PlayError::NotASet);
         }
         
-        self.score += 1;
+        self.players[player].score += 1;
         if self.deck.len() == 0 {
             // No more cards in deck. Remove cards from play and that's it
             sorted_move(&Move(c1, c2, c3)).iter().rev().for_each(|&i| {
@@ -316,6 +801,8 @@ impl Game {
             assert_eq!(self.in_play.len() % 3, 0);
         }
 
+        self.auto_deal();
+
         Ok(())
     }
 
@@ -325,23 +812,239 @@ impl Game {
 
 
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum PlayError {
     InvalidMove,
     NotASet,
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GameStatus {
+    InProgress,
+    Finished {
+        // The cards left on the board, which contain no set
+        leftover: Vec<Card>,
+        score: i32,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DrawError {
+    DeckEmpty,
+    // Only under official rules, where this costs a point
+    SetOnBoard,
+}
+
+// Save files are plain text with one field per line:
+//   set-save 1
+//   player <score> <name> (one line per player, in order)
+//   deck <cards, with the next card to be drawn last>
+//   in_play <cards, in board order>
+//   ranked <true/false> (optional, defaults to true)
+//   rules <casual/official> (optional, defaults to casual)
+//   auto_deals <n> (optional, defaults to 0)
+//   hint_costs <count> <one card> <two cards> (optional, defaults to HintCosts::default())
+//   hints <taken> <level> (optional, defaults to 0 0; level is how far up the ladder the current board is)
+// Cards are written as in Card's Display impl. The undo history is not saved
+const SAVE_HEADER: &str = "set-save 1";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadError {
+    BadHeader,
+    MissingField(&'static str),
+    UnknownField(String),
+    BadPlayer(String),
+    BadRanked(String),
+    BadRules(String),
+    BadAutoDeals(String),
+    BadHintCosts(String),
+    BadHints(String),
+    BadCard(ParseCardError),
+    DuplicateCard(Card),
+    // Cards only ever come and go in threes, and there are always at least 12 in play until the deck runs out
+    BadBoard { in_play: usize, deck: usize },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::BadHeader => write!(f, "Not a save file (expected it to start with \"{}\")", SAVE_HEADER),
+            LoadError::MissingField(field) => write!(f, "Save file is missing the {} field", field),
+            LoadError::UnknownField(field) => write!(f, "Unknown field \"{}\" in save file", field),
+            LoadError::BadPlayer(player) => write!(f, "Invalid player \"{}\" (expected a score and a name)", player),
+            LoadError::BadRanked(ranked) => write!(f, "Invalid ranked value \"{}\" (must be true or false)", ranked),
+            LoadError::BadRules(rules) => write!(f, "Invalid rules \"{}\" (must be casual or official)", rules),
+            LoadError::BadAutoDeals(deals) => write!(f, "Invalid number of automatic deals \"{}\"", deals),
+            LoadError::BadHintCosts(costs) => write!(f, "Invalid hint costs \"{}\" (expected three numbers)", costs),
+            LoadError::BadHints(hints) => write!(f, "Invalid hints \"{}\" (expected the number taken and the level)", hints),
+            LoadError::BadCard(e) => write!(f, "{}", e),
+            LoadError::DuplicateCard(c) => write!(f, "Card {} appears more than once", c),
+            LoadError::BadBoard { in_play, deck } => write!(f, "Impossible to have {} cards in play with {} left in the deck", in_play, deck),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+impl From<ParseCardError> for LoadError {
+    fn from(e: ParseCardError) -> Self {
+        LoadError::BadCard(e)
+    }
+}
+
+fn cards_to_string(cards: &[Card]) -> String {
+    cards.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+impl Game {
+    pub fn to_save_string(&self) -> String {
+        let players: String = self.players.iter().map(|p| format!("player {} {}\n", p.score, p.name)).collect();
+        let rules = match self.rules {
+            Rules::Casual => "casual",
+            Rules::Official => "official",
+        };
+        let costs = self.hint_costs;
+        // The ladder only carries on if the board hasn't changed since the last hint
+        let hint_level = if self.hinted_board == self.in_play { self.hint_level } else { 0 };
+        format!("{}\n{}deck {}\nin_play {}\nranked {}\nrules {}\nauto_deals {}\nhint_costs {} {} {}\nhints {} {}\n",
+                SAVE_HEADER,
+                players,
+                cards_to_string(&self.deck),
+                cards_to_string(&self.in_play),
+                self.ranked,
+                rules,
+                self.auto_deals,
+                costs.count, costs.one_card, costs.two_cards,
+                self.hints_taken, hint_level)
+    }
+
+    pub fn from_save_string(s: &str) -> Result<Self, LoadError> {
+        let mut lines = s.lines().filter(|l| !l.trim().is_empty());
+        if lines.next().map(str::trim) != Some(SAVE_HEADER) {
+            return Err(LoadError::BadHeader);
+        }
+
+        let mut players = vec![];
+        let mut deck = None;
+        let mut in_play = None;
+        let mut ranked = true;
+        let mut rules = Rules::Casual;
+        let mut auto_deals = 0;
+        let mut hint_costs = HintCosts::default();
+        let (mut hints_taken, mut hint_level) = (0, 0);
+
+        for line in lines {
+            let mut words = line.split_whitespace();
+            let field = words.next().expect("Blank lines were filtered out");
+            match field {
+                "player" => {
+                    let value = line.trim()["player".len()..].trim();
+                    // Names can have spaces in them, so they're the whole rest of the line
+                    let player = value.split_once(' ')
+                        .and_then(|(score, name)| Some(Player { name: name.trim().to_string(), score: score.parse::<i32>().ok()? }))
+                        .filter(|p| !p.name.is_empty())
+                        .ok_or_else(|| LoadError::BadPlayer(value.to_string()))?;
+                    players.push(player);
+                }
+                "deck" => deck = Some(words.map(Card::from_str).collect::<Result<Vec<Card>, _>>()?),
+                "in_play" => in_play = Some(words.map(Card::from_str).collect::<Result<Vec<Card>, _>>()?),
+                "ranked" => {
+                    let value = words.next().unwrap_or("");
+                    ranked = value.parse::<bool>().map_err(|_| LoadError::BadRanked(value.to_string()))?;
+                }
+                "rules" => {
+                    rules = match words.next() {
+                        Some("casual") => Rules::Casual,
+                        Some("official") => Rules::Official,
+                        value => return Err(LoadError::BadRules(value.unwrap_or("").to_string())),
+                    };
+                }
+                "auto_deals" => {
+                    let value = words.next().unwrap_or("");
+                    auto_deals = value.parse::<usize>().map_err(|_| LoadError::BadAutoDeals(value.to_string()))?;
+                }
+                "hint_costs" => {
+                    let values: Vec<&str> = words.collect();
+                    hint_costs = match values.iter().map(|v| v.parse::<i32>()).collect::<Result<Vec<i32>, _>>().as_deref() {
+                        Ok(&[count, one_card, two_cards]) => HintCosts { count, one_card, two_cards },
+                        _ => return Err(LoadError::BadHintCosts(values.join(" "))),
+                    };
+                }
+                "hints" => {
+                    let values: Vec<&str> = words.collect();
+                    (hints_taken, hint_level) = match values.iter().map(|v| v.parse::<usize>()).collect::<Result<Vec<usize>, _>>().as_deref() {
+                        Ok(&[taken, level]) => (taken, level),
+                        _ => return Err(LoadError::BadHints(values.join(" "))),
+                    };
+                }
+                _ => return Err(LoadError::UnknownField(field.to_string())),
+            }
+        }
+
+        if players.is_empty() {
+            return Err(LoadError::MissingField("player"));
+        }
+
+        let in_play = in_play.ok_or(LoadError::MissingField("in_play"))?;
+        let mut game = Game {
+            players,
+            deck: deck.ok_or(LoadError::MissingField("deck"))?,
+            hinted_board: if hint_level > 0 { in_play.clone() } else { vec![] },
+            in_play,
+            history: vec![],
+            undone: vec![],
+            ranked,
+            rules,
+            auto_deals,
+            hint_costs,
+            hint_level,
+            hints_taken,
+        };
+
+        // Every card can only be in one place
+        let mut seen = HashSet::new();
+        for c in game.deck.iter().chain(game.in_play.iter()) {
+            if !seen.insert(*c) {
+                return Err(LoadError::DuplicateCard(*c));
+            }
+        }
+
+        let (in_play, deck) = (game.in_play.len(), game.deck.len());
+        if in_play % 3 != 0 || deck % 3 != 0 || (deck > 0 && in_play < 12) {
+            return Err(LoadError::BadBoard { in_play, deck });
+        }
+
+        // A save written by hand (or before the rules changed) might not have had more cards dealt yet
+        game.auto_deal();
+
+        Ok(game)
+    }
+}
+
 
 pub fn find_sets(g: &Game) -> Vec<Move> {
-    // TODO: could maybe be more efficient with a set? But probably not just because of the overhead of a hashset lookup
-    // Could probably be made more efficient with some bithacking nonsense (u128)
+    find_sets_in(&g.in_play)
+}
+
+// Finds every set among the cards, as positions into the slice (each set appears once, in increasing order)
+pub fn find_sets_in(cards: &[Card]) -> Vec<Move> {
+    let indices: Vec<usize> = cards.iter().map(|c| c.to_index()).collect();
+    let mask = card_mask(cards);
+
+    // Where each card is on the board
+    let mut position = [usize::MAX; 81];
+    for (p, &i) in indices.iter().enumerate() {
+        position[i] = p;
+    }
+
     let mut sets = vec![];
 
-    for i in 0..g.in_play.len() {
-        for j in (i+1)..g.in_play.len() {
-            for k in (j+1)..g.in_play.len() {
-                if is_set(&g.in_play[i], &g.in_play[j], &g.in_play[k]) {
-                    sets.push(Move(i, j, k));
-                }
+    for i in 0..indices.len() {
+        for j in (i+1)..indices.len() {
+            let third = third_index(indices[i], indices[j]);
+            // Only count the set from its first two cards so that it is found exactly once
+            if mask & (1 << third) != 0 && position[third] > j {
+                sets.push(Move(i, j, position[third]));
             }
         }
     }
@@ -372,10 +1075,14 @@ pub fn make_deck() -> Vec<Card> {
 }
 
 pub fn make_shuffled_deck() -> Vec<Card> {
+    make_shuffled_deck_with_rng(&mut thread_rng())
+}
+
+pub fn make_shuffled_deck_with_rng<R: Rng + ?Sized>(rng: &mut R) -> Vec<Card> {
     let mut deck = make_deck();
 
     // Shuffle the deck
-    deck.shuffle(&mut thread_rng());
+    deck.shuffle(rng);
 
     deck
 }
@@ -392,8 +1099,64 @@ fn is_set(card1: &Card, card2: &Card, card3: &Card) -> bool {
     )
 }
 
+// Checks every pair for its third card rather than checking every triple, so this is O(n^2) instead of O(n^3)
 pub fn has_set(cards: &[Card]) -> bool {
-    panic!("Not implemented!");
+    let indices: Vec<usize> = cards.iter().map(|c| c.to_index()).collect();
+    let mask = card_mask(cards);
+
+    for i in 0..indices.len() {
+        for j in (i+1)..indices.len() {
+            if mask & (1 << third_index(indices[i], indices[j])) != 0 {
+                return true;
+            }
+        }
+    }
+
+    false
+}
+
+// An attribute that stops three cards from being a set: two of them share a value that the third doesn't have
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Violation {
+    Number { pair: Number, odd: Number },
+    Shape { pair: Shape, odd: Shape },
+    Shading { pair: Shading, odd: Shading },
+    Colour { pair: Colour, odd: Colour },
+}
+
+// Written like "shading: two Full, one Empty"
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Number { pair, odd } => write!(f, "number: two {:?}, one {:?}", pair, odd),
+            Violation::Shape { pair, odd } => write!(f, "shape: two {:?}, one {:?}", pair, odd),
+            Violation::Shading { pair, odd } => write!(f, "shading: two {:?}, one {:?}", pair, odd),
+            Violation::Colour { pair, odd } => write!(f, "colour: two {:?}, one {:?}", pair, odd),
+        }
+    }
+}
+
+// Returns the shared value and the odd one out if exactly two of the values are the same
+fn odd_one_out<T: PartialEq + Copy>(a: T, b: T, c: T) -> Option<(T, T)> {
+    if a == b && b != c {
+        Some((a, c))
+    } else if a == c && a != b {
+        Some((a, b))
+    } else if b == c && a != b {
+        Some((b, a))
+    } else {
+        None
+    }
+}
+
+// Lists every attribute that isn't all the same or all different (empty if the cards are a set)
+pub fn explain(card1: &Card, card2: &Card, card3: &Card) -> Vec<Violation> {
+    [
+        odd_one_out(card1.number, card2.number, card3.number).map(|(pair, odd)| Violation::Number { pair, odd }),
+        odd_one_out(card1.shape, card2.shape, card3.shape).map(|(pair, odd)| Violation::Shape { pair, odd }),
+        odd_one_out(card1.shading, card2.shading, card3.shading).map(|(pair, odd)| Violation::Shading { pair, odd }),
+        odd_one_out(card1.colour, card2.colour, card3.colour).map(|(pair, odd)| Violation::Colour { pair, odd }),
+    ].into_iter().flatten().collect()
 }
 
 #[cfg(test)]
@@ -438,6 +1201,36 @@ mod tests {
 	assert!(!is_set(&Card::new(One, Oval, Half, Green), &Card::new(Two, Wave, Empty, Purple), &Card::new(Three, Wave, Full, Red)));
     }
 
+    #[test]
+    fn explain_set() {
+        assert!(explain(&Card::new(One, Oval, Half, Green), &Card::new(Two, Wave, Empty, Purple), &Card::new(Three, Diamond, Full, Red)).is_empty());
+    }
+
+    #[test]
+    fn explain_not_set() {
+        let violations = explain(&Card::new(One, Oval, Full, Green), &Card::new(Two, Wave, Empty, Green), &Card::new(Three, Oval, Full, Red));
+        assert_eq!(violations, vec![
+            Violation::Shape { pair: Oval, odd: Wave },
+            Violation::Shading { pair: Full, odd: Empty },
+            Violation::Colour { pair: Green, odd: Red },
+        ]);
+        assert_eq!(violations[1].to_string(), "shading: two Full, one Empty");
+    }
+
+    #[test]
+    fn explain_agrees_with_is_set() {
+        let deck = make_deck();
+        for c1 in deck.iter().step_by(7) {
+            for c2 in deck.iter().step_by(5) {
+                for c3 in &deck {
+                    if c1 != c2 && c2 != c3 && c1 != c3 {
+                        assert_eq!(explain(c1, c2, c3).is_empty(), is_set(c1, c2, c3));
+                    }
+                }
+            }
+        }
+    }
+
     #[test]
     fn third_card_test() {
         let deck = make_deck();
@@ -454,6 +1247,426 @@ mod tests {
         }
     }
 
+    #[test]
+    fn has_set_agrees_with_find_sets() {
+        for seed in 0..200 {
+            let game = Game::with_seed(seed);
+            assert_eq!(has_set(game.cards_in_play()), !find_sets(&game).is_empty());
+        }
+    }
+
+    #[test]
+    fn has_set_small_boards() {
+        assert!(!has_set(&[]));
+        assert!(!has_set(&[Card::new(One, Oval, Empty, Red), Card::new(Two, Oval, Empty, Red)]));
+        assert!(has_set(&[Card::new(One, Oval, Empty, Red), Card::new(Two, Wave, Full, Green), Card::new(Two, Oval, Empty, Red), Card::new(Three, Oval, Empty, Red)]));
+        assert!(!has_set(&[Card::new(One, Oval, Empty, Red), Card::new(Two, Wave, Full, Green), Card::new(Two, Oval, Empty, Red), Card::new(Three, Oval, Empty, Green)]));
+    }
+
+    #[test]
+    fn test_card_index() {
+        let deck = make_deck();
+        for (i, c) in deck.iter().enumerate() {
+            assert_eq!(c.to_index(), i);
+            assert_eq!(Card::from_index(i), *c);
+        }
+    }
+
+    #[test]
+    fn third_index_matches_third_card() {
+        let deck = make_deck();
+        for c1 in &deck {
+            for c2 in &deck {
+                if c1 != c2 {
+                    assert_eq!(third_index(c1.to_index(), c2.to_index()), third_card(c1, c2).to_index());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn find_sets_matches_naive_search() {
+        for seed in 0..200 {
+            let mut game = Game::with_seed(seed);
+            game.draw_3().unwrap();
+            let cards = game.cards_in_play();
+            let mut naive = vec![];
+            for i in 0..cards.len() {
+                for j in (i+1)..cards.len() {
+                    for k in (j+1)..cards.len() {
+                        if is_set(&cards[i], &cards[j], &cards[k]) {
+                            naive.push((i, j, k));
+                        }
+                    }
+                }
+            }
+            let fast: Vec<(usize, usize, usize)> = find_sets(&game).into_iter().map(|Move(i, j, k)| (i, j, k)).collect();
+            assert_eq!(fast, naive);
+        }
+    }
+
+    #[test]
+    fn card_string_round_trip() {
+        for c in make_deck() {
+            assert_eq!(c.to_string().parse::<Card>(), Ok(c));
+        }
+        assert_eq!(Card::new(Two, Wave, Half, Red).to_string(), "2WHR");
+        assert!("2WHX".parse::<Card>().is_err());
+        assert!("2WH".parse::<Card>().is_err());
+    }
+
+    #[test]
+    fn save_round_trip() {
+        let mut game = Game::with_seed(5);
+        game.draw_3().unwrap();
+        let Move(a, b, c) = find_sets(&game).pop().expect("15 cards from seed 5 contain a set");
+        assert!(game.attempt_move(&Move(a, b, c)).is_ok());
+
+        let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+        assert_eq!(loaded.deck, game.deck);
+        assert_eq!(loaded.in_play, game.in_play);
+        assert_eq!(loaded.players, game.players);
+    }
+
+    #[test]
+    fn save_with_empty_deck() {
+        let mut game = Game::from_deck(make_deck());
+        game.deck = vec![];
+        game.in_play = vec![Card::new(One, Oval, Empty, Red), Card::new(Two, Oval, Empty, Red), Card::new(Three, Wave, Full, Red)];
+        game.set_players(vec!["Ada Lovelace".to_string(), "Bob".to_string()]);
+        game.players[0].score = -2;
+        game.ranked = false;
+        let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+        assert!(loaded.deck.is_empty());
+        assert_eq!(loaded.in_play, game.in_play);
+        assert_eq!(loaded.players, game.players);
+        assert_eq!(loaded.players[0].get_name(), "Ada Lovelace");
+        assert!(!loaded.is_ranked());
+        assert_eq!(loaded.get_rules(), Rules::Casual);
+        assert_eq!(loaded.get_hint_costs(), HintCosts::default());
+    }
+
+    #[test]
+    fn load_errors() {
+        assert_eq!(Game::from_save_string("hello").unwrap_err(), LoadError::BadHeader);
+        assert_eq!(Game::from_save_string("set-save 1\nplayer 1 Me\ndeck 1OER\n").unwrap_err(), LoadError::MissingField("in_play"));
+        assert_eq!(Game::from_save_string("set-save 1\ndeck 1OER\nin_play\n").unwrap_err(), LoadError::MissingField("player"));
+        assert_eq!(Game::from_save_string("set-save 1\nplayer x Me\n").unwrap_err(), LoadError::BadPlayer("x Me".to_string()));
+        assert_eq!(Game::from_save_string("set-save 1\nplayer 3\n").unwrap_err(), LoadError::BadPlayer("3".to_string()));
+        assert_eq!(Game::from_save_string("set-save 1\nplayer 0 Me\ndeck 1OER\nin_play 1OER\n").unwrap_err(), LoadError::DuplicateCard(Card::new(One, Oval, Empty, Red)));
+        assert!(matches!(Game::from_save_string("set-save 1\nplayer 0 Me\ndeck 1OEX\nin_play\n").unwrap_err(), LoadError::BadCard(_)));
+        assert_eq!(Game::from_save_string("set-save 1\nhint_costs 1 2\n").unwrap_err(), LoadError::BadHintCosts("1 2".to_string()));
+        assert_eq!(Game::from_save_string("set-save 1\nhints 1 -1\n").unwrap_err(), LoadError::BadHints("1 -1".to_string()));
+    }
+
+    #[test]
+    fn load_malformed_board() {
+        let game = Game::with_seed(4);
+        let save = game.to_save_string();
+        let with_board = |deck: &[Card], in_play: &[Card]| {
+            save.lines().map(|l| {
+                if l.starts_with("deck") {
+                    format!("deck {}", cards_to_string(deck))
+                } else if l.starts_with("in_play") {
+                    format!("in_play {}", cards_to_string(in_play))
+                } else {
+                    l.to_string()
+                }
+            }).collect::<Vec<String>>().join("\n")
+        };
+
+        // A card missing from the board
+        let loaded = Game::from_save_string(&with_board(&game.deck, &game.in_play[..11]));
+        assert_eq!(loaded.unwrap_err(), LoadError::BadBoard { in_play: 11, deck: 69 });
+        // A set missing from the board while there are still cards to deal
+        let loaded = Game::from_save_string(&with_board(&game.deck, &game.in_play[..9]));
+        assert_eq!(loaded.unwrap_err(), LoadError::BadBoard { in_play: 9, deck: 69 });
+        // A card missing from the deck
+        let loaded = Game::from_save_string(&with_board(&game.deck[1..], &game.in_play));
+        assert_eq!(loaded.unwrap_err(), LoadError::BadBoard { in_play: 12, deck: 68 });
+        // Fewer than 12 cards is fine once the deck is empty
+        assert!(Game::from_save_string(&with_board(&[], &game.in_play[..9])).is_ok());
+    }
+
+    // Snapshot of everything undo is supposed to restore
+    fn state(g: &Game) -> (Vec<Card>, Vec<Card>, i32) {
+        (g.deck.clone(), g.in_play.clone(), g.get_score())
+    }
+
+    #[test]
+    fn undo_redo_move() {
+        let mut game = Game::with_seed(11);
+        let before = state(&game);
+        let mve = find_sets(&game).pop().expect("Seed 11 deals a board with a set");
+        assert!(game.attempt_move(&mve).is_ok());
+        let after = state(&game);
+
+        let entry = &game.history()[0];
+        assert_eq!(entry.action, Action::Take(mve));
+        assert_eq!(entry.dealt.len(), 3);
+        assert_eq!(entry.score_delta, 1);
+        assert!(entry.taken.is_some());
+
+        game.undo().unwrap();
+        assert_eq!(state(&game), before);
+        assert!(!game.is_ranked());
+        game.redo().unwrap();
+        assert_eq!(state(&game), after);
+        assert!(game.redo().is_err());
+    }
+
+    #[test]
+    fn undo_draw_and_compaction() {
+        let mut game = Game::with_seed(12);
+        let start = state(&game);
+        game.draw_3().unwrap();
+        let drawn = state(&game);
+        let mve = find_sets(&game).pop().expect("15 cards from seed 12 contain a set");
+        assert!(game.attempt_move(&mve).is_ok());
+        assert_eq!(game.cards_in_play().len(), 12);
+        assert!(game.history()[1].dealt.is_empty());
+
+        game.undo().unwrap();
+        assert_eq!(state(&game), drawn);
+        game.undo().unwrap();
+        assert_eq!(state(&game), start);
+        assert!(game.undo().is_err());
+
+        game.redo().unwrap();
+        assert_eq!(state(&game), drawn);
+    }
+
+    #[test]
+    fn undo_bad_guess() {
+        let mut game = Game::with_seed(13);
+        let before = state(&game);
+        let sets = find_sets(&game);
+        let bad = (0..12).flat_map(|i| (i+1..12).map(move |j| (i, j)))
+            .flat_map(|(i, j)| (j+1..12).map(move |k| Move(i, j, k)))
+            .find(|m| !sets.contains(m))
+            .unwrap();
+        assert!(game.attempt_move(&bad).is_err());
+        assert_eq!(game.get_score(), -1);
+        assert!(game.history()[0].taken.is_none());
+
+        // Invalid moves change nothing, so aren't recorded
+        assert!(game.attempt_move(&Move(0, 0, 1)).is_err());
+        assert_eq!(game.history().len(), 1);
+
+        game.undo().unwrap();
+        assert_eq!(state(&game), before);
+    }
+
+    #[test]
+    fn new_action_clears_redo() {
+        let mut game = Game::with_seed(14);
+        game.draw_3().unwrap();
+        game.undo().unwrap();
+        game.draw_3().unwrap();
+        assert!(game.redo().is_err());
+        assert!(!game.is_ranked());
+    }
+
+    #[test]
+    fn per_player_scores() {
+        let mut game = Game::with_seed(15);
+        game.set_players(vec!["Ann".to_string(), "Ben".to_string()]);
+        let sets = find_sets(&game);
+        let bad = (3..12).map(|k| Move(0, 1, k)).find(|m| !sets.contains(m)).unwrap();
+
+        assert!(game.attempt_move_as(1, &bad).is_err());
+        let mve = find_sets(&game).pop().expect("Seed 15 deals a board with a set");
+        assert!(game.attempt_move_as(0, &mve).is_ok());
+        assert_eq!(game.players()[0].get_score(), 1);
+        assert_eq!(game.players()[1].get_score(), -1);
+        assert_eq!(game.get_score(), 0);
+
+        // Undo gives the point back to the right player
+        game.undo().unwrap();
+        game.undo().unwrap();
+        assert_eq!(game.players()[1].get_score(), 0);
+        game.redo().unwrap();
+        assert_eq!(game.players()[1].get_score(), -1);
+        assert_eq!(game.history()[0].player, 1);
+    }
+
+    // Finds a seed whose first board has no set
+    fn seed_without_set() -> u64 {
+        (0..).find(|&seed| !has_set(Game::with_seed(seed).cards_in_play())).unwrap()
+    }
+
+    #[test]
+    fn official_rules_deal_when_no_set() {
+        let mut game = Game::with_seed(seed_without_set());
+        game.set_rules(Rules::Official);
+        assert!(game.cards_in_play().len() >= 15);
+        assert!(has_set(game.cards_in_play()));
+        assert!(game.auto_deals() >= 1);
+        assert_eq!(game.get_score(), 0);
+    }
+
+    #[test]
+    fn official_rules_deal_after_loading() {
+        let mut game = Game::with_seed(seed_without_set());
+        game.set_rules(Rules::Official);
+        let loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+        assert_eq!(loaded.auto_deals(), game.auto_deals());
+        assert_eq!(loaded.cards_in_play(), game.cards_in_play());
+
+        // Saved without the extra cards having been dealt
+        let save = Game::with_seed(seed_without_set()).to_save_string().replace("rules casual", "rules official");
+        let loaded = Game::from_save_string(&save).unwrap();
+        assert_eq!(loaded.cards_in_play(), game.cards_in_play());
+        assert_eq!(loaded.auto_deals(), game.auto_deals());
+        assert_eq!(Game::from_save_string("set-save 1\nauto_deals x\n").unwrap_err(), LoadError::BadAutoDeals("x".to_string()));
+    }
+
+    #[test]
+    fn official_rules_penalise_drawing_with_a_set() {
+        let mut game = Game::with_seed(17);
+        game.set_rules(Rules::Official);
+        let len = game.cards_in_play().len();
+        assert_eq!(game.draw_3(), Err(DrawError::SetOnBoard));
+        assert_eq!(game.cards_in_play().len(), len);
+        assert_eq!(game.get_score(), -1);
+        game.undo().unwrap();
+        assert_eq!(game.get_score(), 0);
+    }
+
+    #[test]
+    fn official_rules_always_leave_a_set() {
+        for seed in 0..20 {
+            let mut game = Game::with_seed(seed);
+            game.set_rules(Rules::Official);
+            while let Some(mve) = find_sets(&game).pop() {
+                let auto_deals = game.auto_deals();
+                assert!(game.attempt_move(&mve).is_ok());
+                if game.remaining_cards() > 0 {
+                    assert!(has_set(game.cards_in_play()));
+                }
+                // Undoing takes back cards that were dealt automatically too
+                if game.auto_deals() > auto_deals {
+                    let len = game.cards_in_play().len();
+                    game.undo().unwrap();
+                    assert_eq!(game.auto_deals(), auto_deals);
+                    game.redo().unwrap();
+                    assert_eq!(game.cards_in_play().len(), len);
+                }
+            }
+            assert_eq!(game.draw_3(), Err(DrawError::DeckEmpty));
+        }
+    }
+
+    #[test]
+    fn games_finish() {
+        for seed in 0..20 {
+            let mut game = Game::with_seed(seed);
+            while game.status() == GameStatus::InProgress {
+                assert!(game.remaining_cards() > 0 || has_set(game.cards_in_play()));
+                match find_sets(&game).pop() {
+                    Some(mve) => assert!(game.attempt_move(&mve).is_ok()),
+                    None => assert!(game.draw_3().is_ok()),
+                }
+            }
+            match game.status() {
+                GameStatus::Finished { leftover, score } => {
+                    assert_eq!(game.remaining_cards(), 0);
+                    assert!(!has_set(&leftover));
+                    assert_eq!(leftover.len() % 3, 0);
+                    assert_eq!(score, game.get_score());
+                    // Every card is either taken (3 per point) or left over, less the points lost to drawing
+                    let draws = game.history().iter().filter(|e| e.action == Action::Draw3).count() as i32;
+                    assert_eq!((81 - leftover.len() as i32) / 3 - draws, score);
+                }
+                GameStatus::InProgress => unreachable!(),
+            }
+        }
+    }
+
+    #[test]
+    fn hint_ladder() {
+        let mut game = Game::with_seed(18);
+        game.set_hint_costs(HintCosts { count: 1, one_card: 2, two_cards: 3 });
+        let sets = find_sets(&game);
+        let Move(c1, c2, _) = sets[0];
+
+        assert_eq!(game.hint(), Hint::Count(sets.len()));
+        assert_eq!(game.get_score(), -1);
+        assert_eq!(game.hint(), Hint::OneCard(c1));
+        assert_eq!(game.get_score(), -3);
+        assert_eq!(game.hint(), Hint::TwoCards(c1, c2));
+        assert_eq!(game.get_score(), -6);
+        // No more to give away, so no more to pay
+        assert_eq!(game.hint(), Hint::TwoCards(c1, c2));
+        assert_eq!(game.get_score(), -6);
+        assert_eq!(game.hints_taken(), 3);
+
+        // A new board starts again from the bottom of the ladder
+        assert!(game.attempt_move(&sets[0]).is_ok());
+        assert!(matches!(game.hint(), Hint::Count(_)));
+    }
+
+    #[test]
+    fn hint_without_sets() {
+        let mut game = Game::with_seed(seed_without_set());
+        assert_eq!(game.hint(), Hint::Count(0));
+        assert_eq!(game.hint(), Hint::Count(0));
+        assert_eq!(game.hints_taken(), 1);
+    }
+
+    #[test]
+    fn undo_hint() {
+        let mut game = Game::with_seed(18);
+        game.set_hint_costs(HintCosts { count: 1, one_card: 2, two_cards: 3 });
+        let Move(c1, _, _) = find_sets(&game)[0];
+        game.hint();
+        assert_eq!(game.hint(), Hint::OneCard(c1));
+        assert_eq!(game.history().len(), 2);
+        assert_eq!(game.history()[1].action, Action::Hint);
+        assert_eq!(game.history()[1].score_delta, -2);
+
+        game.undo().unwrap();
+        assert_eq!(game.get_score(), -1);
+        assert_eq!(game.hints_taken(), 1);
+        game.redo().unwrap();
+        assert_eq!(game.get_score(), -3);
+        assert_eq!(game.hints_taken(), 2);
+
+        // Taking back a free hint still steps back down the ladder
+        game.set_hint_costs(HintCosts { count: 0, one_card: 0, two_cards: 0 });
+        game.hint();
+        game.undo().unwrap();
+        assert!(matches!(game.hint(), Hint::TwoCards(..)));
+        assert_eq!(game.get_score(), -3);
+    }
+
+    #[test]
+    fn save_keeps_hint_costs() {
+        let mut game = Game::with_seed(19);
+        let costs = HintCosts { count: 0, one_card: 2, two_cards: -1 };
+        game.set_hint_costs(costs);
+        assert_eq!(Game::from_save_string(&game.to_save_string()).unwrap().get_hint_costs(), costs);
+    }
+
+    #[test]
+    fn save_keeps_hints() {
+        let mut game = Game::with_seed(18);
+        let Move(c1, c2, _) = find_sets(&game)[0];
+        game.hint();
+        assert_eq!(game.hint(), Hint::OneCard(c1));
+
+        // Carries on up the ladder where it left off
+        let mut loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+        assert_eq!(loaded.hints_taken(), 2);
+        assert_eq!(loaded.hint(), Hint::TwoCards(c1, c2));
+
+        // But a board that has changed since the last hint starts again from the bottom
+        assert!(game.attempt_move(&find_sets(&game)[0]).is_ok());
+        let mut loaded = Game::from_save_string(&game.to_save_string()).unwrap();
+        assert_eq!(loaded.hints_taken(), 2);
+        assert!(matches!(loaded.hint(), Hint::Count(_)));
+    }
+
     #[test]
     fn test_make_deck() {
         let deck = make_deck();
@@ -471,4 +1684,25 @@ mod tests {
         assert_eq!(deck.iter().filter(|&&c| c == Card::new(Three, Wave, Full, Green)).count(), 1);
         assert_eq!(deck.iter().filter(|&&c| c == Card::new(Two, Diamond, Empty, Red)).count(), 1);
     }
+
+    #[test]
+    fn test_with_seed_is_reproducible() {
+        let g1 = Game::with_seed(1234);
+        let g2 = Game::with_seed(1234);
+        assert_eq!(g1.deck, g2.deck);
+        assert_eq!(g1.in_play, g2.in_play);
+        assert_eq!(g1.cards_in_play().len(), 12);
+        assert_eq!(g1.remaining_cards(), 69);
+    }
+
+    #[test]
+    fn test_seeded_moves_are_reproducible() {
+        let mut g1 = Game::with_seed(99);
+        let mut g2 = Game::with_seed(99);
+        let Move(a, b, c) = find_sets(&g1).pop().expect("Seed 99 deals a board with a set");
+        assert!(g1.attempt_move(&Move(a, b, c)).is_ok());
+        assert!(g2.attempt_move(&Move(a, b, c)).is_ok());
+        assert_eq!(g1.in_play, g2.in_play);
+        assert_eq!(g1.get_score(), 1);
+    }
 }
@@ -0,0 +1,179 @@
+use std::error::Error;
+use std::fmt;
+
+use rand::Rng;
+use rand::seq::SliceRandom;
+
+use super::backend::{Card, third_index};
+
+// The biggest set of cards with no set in it has 20 cards
+pub const MAX_CARDS_WITHOUT_SET: usize = 20;
+
+// The most sets there can be on a board of each size up to 13 cards, and the numbers of sets below that which still
+// no board has. Found by going through every board up to symmetry; bigger boards only get the bound from counting pairs
+const MAX_SETS: [usize; 14] = [0, 0, 0, 1, 1, 2, 3, 5, 8, 12, 12, 13, 14, 16];
+const MISSING_SETS: [(usize, usize); 10] = [(7, 4), (8, 6), (8, 7), (9, 7), (9, 9), (9, 10), (9, 11), (10, 10), (10, 11), (11, 11)];
+
+// The search restarts with a new random card order every so often, since an unlucky start can take forever
+const NODES_PER_ATTEMPT: usize = 20_000;
+const MAX_ATTEMPTS: usize = 200;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum GenerateError {
+    // No board like this can exist
+    Infeasible { cards: usize, sets: usize },
+    // Gave up looking (there might still be such a board)
+    NotFound { cards: usize, sets: usize },
+}
+
+impl fmt::Display for GenerateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GenerateError::Infeasible { cards, sets } => write!(f, "There is no board of {} cards with exactly {} sets", cards, sets),
+            GenerateError::NotFound { cards, sets } => write!(f, "Couldn't find a board of {} cards with exactly {} sets", cards, sets),
+        }
+    }
+}
+
+impl Error for GenerateError {}
+
+// Generates a board of `cards` cards with exactly `sets` sets on it, in a random order
+pub fn generate_board<R: Rng + ?Sized>(rng: &mut R, cards: usize, sets: usize) -> Result<Vec<Card>, GenerateError> {
+    if !is_feasible(cards, sets) {
+        return Err(GenerateError::Infeasible { cards, sets });
+    }
+
+    let mut order: Vec<usize> = (0..81).collect();
+    for _ in 0..MAX_ATTEMPTS {
+        order.shuffle(rng);
+        let mut search = Search {
+            order: &order,
+            cards,
+            sets,
+            nodes_left: NODES_PER_ATTEMPT,
+            board: Vec::with_capacity(cards),
+            completes: [0; 81],
+            found: 0,
+        };
+        if search.extend(0) {
+            let mut board: Vec<Card> = search.board.into_iter().map(Card::from_index).collect();
+            board.shuffle(rng);
+            return Ok(board);
+        }
+    }
+    Err(GenerateError::NotFound { cards, sets })
+}
+
+fn is_feasible(cards: usize, sets: usize) -> bool {
+    if let Some(&max) = MAX_SETS.get(cards) {
+        return sets <= max && !MISSING_SETS.contains(&(cards, sets));
+    }
+    // Every pair of cards is in exactly one set, so each set uses up 3 of the pairs
+    cards <= 81 && sets <= cards * (cards - 1) / 6 && (sets > 0 || cards <= MAX_CARDS_WITHOUT_SET)
+}
+
+// Depth first search over boards, adding cards in the order given
+struct Search<'a> {
+    order: &'a [usize],
+    cards: usize,
+    sets: usize,
+    nodes_left: usize,
+    board: Vec<usize>,
+    // How many pairs on the board each card would complete a set with
+    completes: [usize; 81],
+    // How many sets are on the board
+    found: usize,
+}
+
+impl Search<'_> {
+    // Tries to fill the board using cards from position `start` onwards in the order
+    fn extend(&mut self, start: usize) -> bool {
+        let have = self.board.len();
+        if have == self.cards {
+            return self.found == self.sets;
+        }
+        if self.nodes_left == 0 {
+            return false;
+        }
+        self.nodes_left -= 1;
+
+        // The sets through a new card split the rest of the board into pairs, so
+        // the ith card added from here can add at most (have + i) / 2 sets
+        let to_add = self.cards - have;
+        let most: usize = (0..to_add).map(|i| (have + i) / 2).sum();
+        if self.found + most < self.sets {
+            return false;
+        }
+
+        for p in start..=(81 - to_add) {
+            let card = self.order[p];
+            let added = self.completes[card];
+            if self.found + added > self.sets {
+                continue;
+            }
+
+            for &other in &self.board {
+                self.completes[third_index(card, other)] += 1;
+            }
+            self.board.push(card);
+            self.found += added;
+
+            if self.extend(p + 1) {
+                return true;
+            }
+
+            self.found -= added;
+            self.board.pop();
+            for &other in &self.board {
+                self.completes[third_index(card, other)] -= 1;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+    use super::super::backend::{card_mask, find_sets_in};
+
+    #[test]
+    fn boards_have_the_right_number_of_sets() {
+        let mut rng = StdRng::seed_from_u64(13);
+        for (cards, sets) in [(3, 0), (3, 1), (9, 12), (12, 0), (12, 6), (12, 14), (15, 2), (20, 0), (81, 1080)] {
+            let board = generate_board(&mut rng, cards, sets).unwrap();
+            assert_eq!(board.len(), cards);
+            assert_eq!(card_mask(&board).count_ones() as usize, cards);
+            assert_eq!(find_sets_in(&board).len(), sets, "{} cards with {} sets", cards, sets);
+        }
+    }
+
+    #[test]
+    fn infeasible_boards() {
+        let mut rng = StdRng::seed_from_u64(13);
+        assert_eq!(generate_board(&mut rng, 21, 0), Err(GenerateError::Infeasible { cards: 21, sets: 0 }));
+        assert_eq!(generate_board(&mut rng, 82, 0), Err(GenerateError::Infeasible { cards: 82, sets: 0 }));
+        assert_eq!(generate_board(&mut rng, 12, 23), Err(GenerateError::Infeasible { cards: 12, sets: 23 }));
+        assert_eq!(generate_board(&mut rng, 2, 1), Err(GenerateError::Infeasible { cards: 2, sets: 1 }));
+        // Within the pair bound, but more than 12 cards can hold
+        assert_eq!(generate_board(&mut rng, 12, 15), Err(GenerateError::Infeasible { cards: 12, sets: 15 }));
+        assert_eq!(generate_board(&mut rng, 9, 10), Err(GenerateError::Infeasible { cards: 9, sets: 10 }));
+    }
+
+    #[test]
+    fn small_boards_match_the_table() {
+        let mut rng = StdRng::seed_from_u64(13);
+        for (cards, &max) in MAX_SETS.iter().enumerate() {
+            for sets in 0..=max + 1 {
+                let board = generate_board(&mut rng, cards, sets);
+                if sets > max || MISSING_SETS.contains(&(cards, sets)) {
+                    assert_eq!(board, Err(GenerateError::Infeasible { cards, sets }));
+                } else {
+                    assert_eq!(find_sets_in(&board.unwrap()).len(), sets, "{} cards with {} sets", cards, sets);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,218 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use super::backend::{Card, Move, PlayError, find_sets_in};
+use super::generator::{GenerateError, generate_board};
+
+pub const PUZZLE_SIZE: usize = 12;
+pub const DEFAULT_PUZZLE_SETS: usize = 6;
+
+// A day in the (proleptic Gregorian) calendar
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Date {
+    pub year: i64,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    // Today's date in UTC, so that everyone gets the same puzzle at the same time
+    pub fn today() -> Self {
+        let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        Date::from_days_since_epoch((secs / 86400) as i64)
+    }
+
+    // Days since 1970-01-01 (see http://howardhinnant.github.io/date_algorithms.html)
+    pub fn days_since_epoch(&self) -> i64 {
+        let year = if self.month <= 2 { self.year - 1 } else { self.year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = self.month as i64;
+        let day_of_year = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5 + self.day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146097 + day_of_era - 719468
+    }
+
+    pub fn from_days_since_epoch(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        Date { year, month, day }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseDateError(pub String);
+
+impl fmt::Display for ParseDateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid date \"{}\" (expected YYYY-MM-DD)", self.0)
+    }
+}
+
+impl Error for ParseDateError {}
+
+impl FromStr for Date {
+    type Err = ParseDateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let err = || ParseDateError(s.to_string());
+        let parts: Vec<&str> = s.trim().split('-').collect();
+        let (year, month, day) = match parts[..] {
+            [y, m, d] => (
+                y.parse::<i64>().map_err(|_| err())?,
+                m.parse::<u32>().map_err(|_| err())?,
+                d.parse::<u32>().map_err(|_| err())?,
+            ),
+            _ => return Err(err()),
+        };
+        let date = Date { year, month, day };
+        // Rules out things like the 31st of February, which would silently become a day in March
+        if !(1..=12).contains(&month) || day == 0 || Date::from_days_since_epoch(date.days_since_epoch()) != date {
+            return Err(err());
+        }
+        Ok(date)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Guess {
+    New,
+    AlreadyFound,
+}
+
+// A fixed board where the goal is to find every set
+#[derive(Clone, Debug)]
+pub struct Puzzle {
+    board: Vec<Card>,
+    sets: Vec<Move>,
+    found: Vec<Move>,
+}
+
+impl Puzzle {
+    // The puzzle for a given day. Everyone asking for the same date and number of sets gets the same board
+    pub fn for_date(date: Date, num_sets: usize) -> Result<Self, GenerateError> {
+        let seed = (date.days_since_epoch() as u64) << 8 | num_sets as u64;
+        // Not StdRng, which can change between versions of rand and so give teammates different boards
+        Puzzle::with_rng(&mut ChaCha8Rng::seed_from_u64(seed), num_sets)
+    }
+
+    pub fn with_rng<R: Rng + ?Sized>(rng: &mut R, num_sets: usize) -> Result<Self, GenerateError> {
+        let board = generate_board(rng, PUZZLE_SIZE, num_sets)?;
+        let sets = find_sets_in(&board);
+        Ok(Puzzle {
+            board,
+            sets,
+            found: vec![],
+        })
+    }
+
+    pub fn get_board(&self) -> &[Card] {
+        &self.board[..]
+    }
+
+    pub fn num_sets(&self) -> usize {
+        self.sets.len()
+    }
+
+    pub fn get_found(&self) -> &[Move] {
+        &self.found[..]
+    }
+
+    pub fn is_solved(&self) -> bool {
+        self.found.len() == self.sets.len()
+    }
+
+    pub fn guess(&mut self, mve: &Move) -> Result<Guess, PlayError> {
+        let mut v = [mve.0, mve.1, mve.2];
+        v.sort();
+        if v[0] == v[1] || v[1] == v[2] || v[2] >= self.board.len() {
+            return Err(PlayError::InvalidMove);
+        }
+        // Sets are stored in increasing order, so the sorted guess can be compared directly
+        let mve = Move(v[0], v[1], v[2]);
+        if self.found.contains(&mve) {
+            Ok(Guess::AlreadyFound)
+        } else if self.sets.contains(&mve) {
+            self.found.push(mve);
+            Ok(Guess::New)
+        } else {
+            Err(PlayError::NotASet)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates() {
+        assert_eq!(Date { year: 1970, month: 1, day: 1 }.days_since_epoch(), 0);
+        assert_eq!(Date { year: 2000, month: 3, day: 1 }.days_since_epoch(), 11017);
+        assert_eq!(Date::from_days_since_epoch(-1), Date { year: 1969, month: 12, day: 31 });
+        for days in [-800000, -1, 0, 59, 10956, 20000, 800000] {
+            assert_eq!(Date::from_days_since_epoch(days).days_since_epoch(), days);
+        }
+        assert_eq!("2024-02-29".parse::<Date>().unwrap().to_string(), "2024-02-29");
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert!("2024-13-01".parse::<Date>().is_err());
+        assert!("2024-1".parse::<Date>().is_err());
+    }
+
+    #[test]
+    fn puzzles_are_daily() {
+        let date = Date { year: 2024, month: 5, day: 17 };
+        let puzzle = Puzzle::for_date(date, 6).unwrap();
+        assert_eq!(puzzle.get_board().len(), PUZZLE_SIZE);
+        assert_eq!(find_sets_in(puzzle.get_board()).len(), 6);
+        assert_eq!(puzzle.get_board(), Puzzle::for_date(date, 6).unwrap().get_board());
+        assert_ne!(puzzle.get_board(), Puzzle::for_date(Date { year: 2024, month: 5, day: 18 }, 6).unwrap().get_board());
+        // Pinned, so that anything that would give teammates different boards fails here
+        let pinned: Vec<Card> = "3OFP 2WEG 3WHR 1OHR 1OEG 2OFG 3WEG 2DHR 3DHG 3OEP 3WFP 3DEG".split(' ').map(|c| c.parse().unwrap()).collect();
+        assert_eq!(puzzle.get_board(), &pinned[..]);
+    }
+
+    #[test]
+    fn finding_sets() {
+        let mut puzzle = Puzzle::for_date(Date { year: 2024, month: 1, day: 1 }, 3).unwrap();
+        let sets = find_sets_in(puzzle.get_board());
+
+        let Move(c1, c2, c3) = sets[0];
+        assert_eq!(puzzle.guess(&Move(c3, c1, c2)), Ok(Guess::New));
+        assert_eq!(puzzle.guess(&sets[0]), Ok(Guess::AlreadyFound));
+        assert_eq!(puzzle.guess(&Move(c1, c1, c2)), Err(PlayError::InvalidMove));
+        assert_eq!(puzzle.guess(&Move(c1, c2, PUZZLE_SIZE)), Err(PlayError::InvalidMove));
+        let not_a_set = (2..PUZZLE_SIZE).map(|c| Move(0, 1, c)).find(|m| !sets.contains(m)).unwrap();
+        assert_eq!(puzzle.guess(&not_a_set), Err(PlayError::NotASet));
+        assert!(!puzzle.is_solved());
+
+        for mve in &sets[1..] {
+            assert_eq!(puzzle.guess(mve), Ok(Guess::New));
+        }
+        assert!(puzzle.is_solved());
+        assert_eq!(puzzle.get_found().len(), 3);
+    }
+
+    #[test]
+    fn impossible_puzzle() {
+        assert_eq!(Puzzle::for_date(Date { year: 2024, month: 1, day: 1 }, 23).unwrap_err(), GenerateError::Infeasible { cards: PUZZLE_SIZE, sets: 23 });
+    }
+}
@@ -0,0 +1,255 @@
+use std::error::Error;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use super::backend::{Game, LoadError, Move};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+// Events that change scores say which player they belong to
+pub enum EventKind {
+    SetFound(usize, Move),
+    BadGuess(usize, Move),
+    Draw3(usize),
+    Hint(usize),
+    Undo,
+    Redo,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Event {
+    // Time since the start of the game
+    pub time: Duration,
+    pub kind: EventKind,
+}
+
+// A recording of a game: the state it started in, and everything the player did
+#[derive(Clone, Debug)]
+pub struct Replay {
+    start: Game,
+    events: Vec<Event>,
+}
+
+impl Replay {
+    pub fn get_start(&self) -> &Game {
+        &self.start
+    }
+
+    pub fn get_events(&self) -> &[Event] {
+        &self.events[..]
+    }
+
+    // Every state the game went through, starting with the initial state and then one per event
+    pub fn boards(&self) -> Vec<Game> {
+        let mut game = self.start.clone();
+        let mut boards = vec![game.clone()];
+        for event in &self.events {
+            match event.kind {
+                EventKind::SetFound(player, mve) | EventKind::BadGuess(player, mve) => {
+                    let _ = game.attempt_move_as(player, &mve);
+                }
+                EventKind::Draw3(player) => {
+                    let _ = game.draw_3_as(player);
+                }
+                EventKind::Undo => {
+                    let _ = game.undo();
+                }
+                EventKind::Redo => {
+                    let _ = game.redo();
+                }
+                EventKind::Hint(player) => {
+                    game.hint_as(player);
+                }
+            }
+            boards.push(game.clone());
+        }
+        boards
+    }
+}
+
+// Records events as a game is played
+pub struct Recorder {
+    replay: Replay,
+    started: Instant,
+}
+
+impl Recorder {
+    pub fn new(start: &Game) -> Self {
+        Recorder {
+            replay: Replay {
+                start: start.clone(),
+                events: vec![],
+            },
+            started: Instant::now(),
+        }
+    }
+
+    pub fn record(&mut self, kind: EventKind) {
+        self.replay.events.push(Event {
+            time: self.started.elapsed(),
+            kind,
+        });
+    }
+
+    pub fn get_replay(&self) -> &Replay {
+        &self.replay
+    }
+}
+
+// Replay files are the save file of the starting state, followed by one event per line:
+//   set-replay 1
+//   <save file>
+//   events
+//   <seconds since start> set|bad <player> <three card positions>
+//   <seconds since start> draw|hint <player>
+//   <seconds since start> undo|redo
+const REPLAY_HEADER: &str = "set-replay 1";
+const EVENTS_MARKER: &str = "events";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum ReplayError {
+    BadHeader,
+    MissingEvents,
+    BadStart(LoadError),
+    BadEvent(String),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::BadHeader => write!(f, "Not a replay file (expected it to start with \"{}\")", REPLAY_HEADER),
+            ReplayError::MissingEvents => write!(f, "Replay file has no \"{}\" line", EVENTS_MARKER),
+            ReplayError::BadStart(e) => write!(f, "Invalid starting game: {}", e),
+            ReplayError::BadEvent(line) => write!(f, "Invalid event \"{}\"", line),
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl fmt::Display for Event {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:.3} ", self.time.as_secs_f64())?;
+        match self.kind {
+            EventKind::SetFound(player, Move(c1, c2, c3)) => write!(f, "set {} {} {} {}", player, c1, c2, c3),
+            EventKind::BadGuess(player, Move(c1, c2, c3)) => write!(f, "bad {} {} {} {}", player, c1, c2, c3),
+            EventKind::Draw3(player) => write!(f, "draw {}", player),
+            EventKind::Hint(player) => write!(f, "hint {}", player),
+            EventKind::Undo => write!(f, "undo"),
+            EventKind::Redo => write!(f, "redo"),
+        }
+    }
+}
+
+fn parse_event(line: &str) -> Result<Event, ReplayError> {
+    let err = || ReplayError::BadEvent(line.to_string());
+    let words: Vec<&str> = line.split_whitespace().collect();
+
+    let secs = words.first().and_then(|w| w.parse::<f64>().ok()).filter(|s| s.is_finite() && *s >= 0.).ok_or_else(err)?;
+    let numbers = words.iter().skip(2).map(|w| w.parse::<usize>()).collect::<Result<Vec<usize>, _>>().map_err(|_| err())?;
+
+    let kind = match (words.get(1).copied(), &numbers[..]) {
+        (Some("set"), &[player, c1, c2, c3]) => EventKind::SetFound(player, Move(c1, c2, c3)),
+        (Some("bad"), &[player, c1, c2, c3]) => EventKind::BadGuess(player, Move(c1, c2, c3)),
+        (Some("draw"), &[player]) => EventKind::Draw3(player),
+        (Some("hint"), &[player]) => EventKind::Hint(player),
+        (Some("undo"), []) => EventKind::Undo,
+        (Some("redo"), []) => EventKind::Redo,
+        _ => return Err(err()),
+    };
+
+    Ok(Event {
+        time: Duration::from_secs_f64(secs),
+        kind,
+    })
+}
+
+impl Replay {
+    pub fn to_replay_string(&self) -> String {
+        let mut out = format!("{}\n{}{}\n", REPLAY_HEADER, self.start.to_save_string(), EVENTS_MARKER);
+        for event in &self.events {
+            out.push_str(&event.to_string());
+            out.push('\n');
+        }
+        out
+    }
+
+    pub fn from_replay_string(s: &str) -> Result<Self, ReplayError> {
+        let lines: Vec<&str> = s.lines().filter(|l| !l.trim().is_empty()).collect();
+        if lines.first().map(|l| l.trim()) != Some(REPLAY_HEADER) {
+            return Err(ReplayError::BadHeader);
+        }
+        let marker = lines.iter().position(|l| l.trim() == EVENTS_MARKER).ok_or(ReplayError::MissingEvents)?;
+
+        let start = Game::from_save_string(&lines[1..marker].join("\n")).map_err(ReplayError::BadStart)?;
+        let events = lines[marker+1..].iter().map(|l| parse_event(l)).collect::<Result<Vec<Event>, _>>()?;
+
+        // Every event has to belong to a player who is actually in the game
+        for (event, line) in events.iter().zip(&lines[marker+1..]) {
+            match event.kind {
+                EventKind::SetFound(player, _) | EventKind::BadGuess(player, _) | EventKind::Draw3(player) | EventKind::Hint(player) if player >= start.players().len() => {
+                    return Err(ReplayError::BadEvent(line.to_string()));
+                }
+                _ => (),
+            }
+        }
+
+        Ok(Replay { start, events })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::backend::find_sets;
+
+    #[test]
+    fn replay_round_trip() {
+        let mut game = Game::with_seed(21);
+        let mut recorder = Recorder::new(&game);
+
+        let mve = find_sets(&game).pop().expect("Seed 21 deals a board with a set");
+        assert!(game.attempt_move(&mve).is_ok());
+        recorder.record(EventKind::SetFound(0, mve));
+        recorder.record(EventKind::Hint(0));
+        game.undo().unwrap();
+        recorder.record(EventKind::Undo);
+
+        let replay = Replay::from_replay_string(&recorder.get_replay().to_replay_string()).unwrap();
+        let kinds: Vec<EventKind> = replay.get_events().iter().map(|e| e.kind).collect();
+        assert_eq!(kinds, vec![EventKind::SetFound(0, mve), EventKind::Hint(0), EventKind::Undo]);
+        assert_eq!(replay.get_start().cards_in_play(), Game::with_seed(21).cards_in_play());
+    }
+
+    #[test]
+    fn boards_follow_events() {
+        let mut game = Game::with_seed(22);
+        let mut recorder = Recorder::new(&game);
+
+        assert!(game.draw_3().is_ok());
+        recorder.record(EventKind::Draw3(0));
+        let mve = find_sets(&game).pop().expect("15 cards from seed 22 contain a set");
+        assert!(game.attempt_move(&mve).is_ok());
+        recorder.record(EventKind::SetFound(0, mve));
+        game.hint();
+        game.hint();
+        recorder.record(EventKind::Hint(0));
+        recorder.record(EventKind::Hint(0));
+
+        let boards = recorder.get_replay().boards();
+        assert_eq!(boards.len(), 5);
+        assert_eq!(boards[0].cards_in_play().len(), 12);
+        assert_eq!(boards[1].cards_in_play().len(), 15);
+        assert_eq!(boards[4].cards_in_play(), game.cards_in_play());
+        assert_eq!(boards[4].get_score(), game.get_score());
+    }
+
+    #[test]
+    fn replay_errors() {
+        assert_eq!(Replay::from_replay_string("set-save 1").unwrap_err(), ReplayError::BadHeader);
+        let start = Game::with_seed(1).to_save_string();
+        assert_eq!(Replay::from_replay_string(&format!("{}\n{}", REPLAY_HEADER, start)).unwrap_err(), ReplayError::MissingEvents);
+        assert_eq!(Replay::from_replay_string(&format!("{}\n{}events\n1.5 set 0 1 2\n", REPLAY_HEADER, start)).unwrap_err(), ReplayError::BadEvent("1.5 set 0 1 2".to_string()));
+        assert!(Replay::from_replay_string(&format!("{}\n{}events\n", REPLAY_HEADER, start)).unwrap().get_events().is_empty());
+        assert_eq!(Replay::from_replay_string(&format!("{}\n{}events\n1.5 draw 1\n", REPLAY_HEADER, start)).unwrap_err(), ReplayError::BadEvent("1.5 draw 1".to_string()));
+    }
+}
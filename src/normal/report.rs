@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+use super::analysis::{GameStats, Histogram, FirstSet, KeepSets, RandomSet, all_caps, count_set_free_boards, max_cap_size, most_cards_with_no_set_fast_with_rng, run_parallel, simulate_games};
+use super::backend::Card;
+
+// What the analyze subcommand can do
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Analysis {
+    // How many cards a random deck gets through before it is forced to have a set
+    Caps,
+    // Statistics from playing complete games
+    Games(StrategyKind),
+    // Every biggest collection of cards with no set in it
+    MaxCaps,
+    // Exactly how many boards of each size have no set
+    SetFree,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum StrategyKind {
+    #[default]
+    First,
+    Random,
+    Keep,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Table,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct ParseOptionError {
+    kind: &'static str,
+    value: String,
+    expected: &'static str,
+}
+
+impl fmt::Display for ParseOptionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Unknown {} \"{}\" (expected {})", self.kind, self.value, self.expected)
+    }
+}
+
+impl Error for ParseOptionError {}
+
+impl FromStr for Analysis {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "caps" => Ok(Analysis::Caps),
+            "games" => Ok(Analysis::Games(StrategyKind::default())),
+            "max-caps" => Ok(Analysis::MaxCaps),
+            "set-free" => Ok(Analysis::SetFree),
+            _ => Err(ParseOptionError { kind: "analysis", value: s.to_string(), expected: "caps, games, max-caps or set-free" }),
+        }
+    }
+}
+
+impl FromStr for StrategyKind {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first" => Ok(StrategyKind::First),
+            "random" => Ok(StrategyKind::Random),
+            "keep" => Ok(StrategyKind::Keep),
+            _ => Err(ParseOptionError { kind: "strategy", value: s.to_string(), expected: "first, random or keep" }),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = ParseOptionError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(Format::Table),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            _ => Err(ParseOptionError { kind: "format", value: s.to_string(), expected: "table, csv or json" }),
+        }
+    }
+}
+
+// The results of an analysis, ready to be written out
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Report {
+    // Settings the results depend on, like the seed
+    pub parameters: Vec<(String, String)>,
+    pub histograms: Vec<(String, Histogram)>,
+    pub boards: Vec<Vec<Card>>,
+    // Any other results, as a table
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+// Runs an analysis. Monte Carlo analyses take `samples` samples from `seed` over `threads` threads
+pub fn run_analysis<P: FnMut(usize, usize)>(analysis: Analysis, samples: usize, seed: u64, threads: usize, progress: P) -> Report {
+    let mut report = Report::default();
+    let mut parameter = |name: &str, value: String| report.parameters.push((name.to_string(), value));
+    match analysis {
+        Analysis::Caps => {
+            parameter("analysis", "caps".to_string());
+            parameter("seed", seed.to_string());
+            parameter("samples", samples.to_string());
+            let histogram = run_parallel(samples, threads, seed, most_cards_with_no_set_fast_with_rng, progress);
+            report.histograms.push(("cards_without_set".to_string(), histogram));
+        }
+        Analysis::Games(strategy) => {
+            parameter("analysis", "games".to_string());
+            parameter("seed", seed.to_string());
+            parameter("samples", samples.to_string());
+            let stats: GameStats = match strategy {
+                StrategyKind::First => {
+                    parameter("strategy", "first".to_string());
+                    simulate_games(samples, threads, seed, &FirstSet, progress)
+                }
+                StrategyKind::Random => {
+                    parameter("strategy", "random".to_string());
+                    simulate_games(samples, threads, seed, &RandomSet, progress)
+                }
+                StrategyKind::Keep => {
+                    parameter("strategy", "keep".to_string());
+                    simulate_games(samples, threads, seed, &KeepSets, progress)
+                }
+            };
+            if let Some(probability) = stats.no_set_probability() {
+                report.parameters.push(("no_set_probability".to_string(), probability.to_string()));
+            }
+            report.histograms.push(("sets_on_board".to_string(), stats.sets_on_board));
+            report.histograms.push(("forced_draws".to_string(), stats.forced_draws));
+            report.histograms.push(("cards_left".to_string(), stats.cards_left));
+        }
+        Analysis::MaxCaps => {
+            parameter("analysis", "max-caps".to_string());
+            let size = max_cap_size();
+            parameter("max_cards_without_set", size.to_string());
+            report.boards = all_caps(size);
+        }
+        Analysis::SetFree => {
+            parameter("analysis", "set-free".to_string());
+            report.columns = ["cards", "set_free", "types", "total", "probability"].iter().map(|c| c.to_string()).collect();
+            // Boards of fewer than 3 cards can't have a set anyway
+            report.rows = count_set_free_boards(20).iter().skip(3)
+                .map(|c| vec![c.cards.to_string(), c.set_free.to_string(), c.types.to_string(), c.total.to_string(), c.probability().to_string()])
+                .collect();
+        }
+    }
+    report
+}
+
+fn cards_string(cards: &[Card]) -> String {
+    cards.iter().map(|c| c.to_string()).collect::<Vec<String>>().join(" ")
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+impl Report {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Table => self.to_table(),
+            Format::Csv => self.to_csv(),
+            Format::Json => self.to_json(),
+        }
+    }
+
+    fn to_table(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.parameters {
+            out.push_str(&format!("{}: {}\n", name, value));
+        }
+        for (name, histogram) in &self.histograms {
+            match histogram.mean() {
+                Some(mean) => out.push_str(&format!("\n{} (mean {:.3}):\n{}", name, mean, histogram)),
+                None => out.push_str(&format!("\n{}: no samples\n", name)),
+            }
+        }
+        if !self.boards.is_empty() {
+            out.push('\n');
+        }
+        for board in &self.boards {
+            out.push_str(&format!("{}\n", cards_string(board)));
+        }
+        if !self.columns.is_empty() {
+            let widths: Vec<usize> = (0..self.columns.len())
+                .map(|i| self.rows.iter().map(|r| r[i].len()).chain([self.columns[i].len()]).max().unwrap_or(0))
+                .collect();
+            out.push('\n');
+            for row in [&self.columns].into_iter().chain(&self.rows) {
+                let cells: Vec<String> = row.iter().zip(&widths).map(|(cell, &width)| format!("{:>width$}", cell, width = width)).collect();
+                out.push_str(&format!("{}\n", cells.join("  ")));
+            }
+        }
+        out
+    }
+
+    // One table per kind of result, with the parameters as comments at the top
+    fn to_csv(&self) -> String {
+        let mut out = String::new();
+        for (name, value) in &self.parameters {
+            out.push_str(&format!("# {}: {}\n", name, value));
+        }
+        if !self.histograms.is_empty() {
+            out.push_str("statistic,value,count\n");
+            for (name, histogram) in &self.histograms {
+                for (value, count) in histogram.counts().iter().enumerate() {
+                    out.push_str(&format!("{},{},{}\n", name, value, count));
+                }
+            }
+        }
+        if !self.boards.is_empty() {
+            out.push_str("board,cards\n");
+            for (i, board) in self.boards.iter().enumerate() {
+                out.push_str(&format!("{},{}\n", i, cards_string(board)));
+            }
+        }
+        if !self.columns.is_empty() {
+            for row in [&self.columns].into_iter().chain(&self.rows) {
+                out.push_str(&format!("{}\n", row.join(",")));
+            }
+        }
+        out
+    }
+
+    fn to_json(&self) -> String {
+        let parameters: Vec<String> = self.parameters.iter()
+            .map(|(name, value)| format!("{}: {}", json_string(name), json_string(value)))
+            .collect();
+        let histograms: Vec<String> = self.histograms.iter()
+            .map(|(name, histogram)| {
+                let counts: Vec<String> = histogram.counts().iter().map(|c| c.to_string()).collect();
+                let mean = histogram.mean().map_or("null".to_string(), |m| m.to_string());
+                format!("{}: {{\"total\": {}, \"mean\": {}, \"counts\": [{}]}}", json_string(name), histogram.total(), mean, counts.join(", "))
+            })
+            .collect();
+        let boards: Vec<String> = self.boards.iter()
+            .map(|board| format!("[{}]", board.iter().map(|c| json_string(&c.to_string())).collect::<Vec<String>>().join(", ")))
+            .collect();
+        // Table cells are all numbers
+        let rows: Vec<String> = self.rows.iter()
+            .map(|row| format!("{{{}}}", self.columns.iter().zip(row).map(|(column, cell)| format!("{}: {}", json_string(column), cell)).collect::<Vec<String>>().join(", ")))
+            .collect();
+        format!("{{\"parameters\": {{{}}}, \"histograms\": {{{}}}, \"boards\": [{}], \"rows\": [{}]}}\n", parameters.join(", "), histograms.join(", "), boards.join(", "), rows.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_report() -> Report {
+        let mut histogram = Histogram::new();
+        for v in [1, 1, 3] {
+            histogram.add(v);
+        }
+        Report {
+            parameters: vec![("seed".to_string(), "7".to_string())],
+            histograms: vec![("sets".to_string(), histogram)],
+            boards: vec![vec![Card::from_index(0), Card::from_index(1)]],
+            columns: vec!["cards".to_string(), "count".to_string()],
+            rows: vec![vec!["3".to_string(), "10".to_string()], vec!["12".to_string(), "5".to_string()]],
+        }
+    }
+
+    #[test]
+    fn formats() {
+        let report = sample_report();
+        assert_eq!(report.render(Format::Csv), "# seed: 7\nstatistic,value,count\nsets,0,0\nsets,1,2\nsets,2,0\nsets,3,1\nboard,cards\n0,1OER 1OEG\ncards,count\n3,10\n12,5\n");
+        assert_eq!(report.render(Format::Json),
+                   "{\"parameters\": {\"seed\": \"7\"}, \"histograms\": {\"sets\": {\"total\": 3, \"mean\": 1.6666666666666667, \"counts\": [0, 2, 0, 1]}}, \"boards\": [[\"1OER\", \"1OEG\"]], \"rows\": [{\"cards\": 3, \"count\": 10}, {\"cards\": 12, \"count\": 5}]}\n");
+        let table = report.render(Format::Table);
+        assert!(table.starts_with("seed: 7\n\nsets (mean 1.667):\n1: 2 ("));
+        assert!(table.ends_with("\ncards  count\n    3     10\n   12      5\n"));
+        assert_eq!(json_string("a\"b\\\n"), "\"a\\\"b\\\\\\u000a\"");
+    }
+
+    #[test]
+    fn parse_options() {
+        assert_eq!("games".parse::<Analysis>(), Ok(Analysis::Games(StrategyKind::First)));
+        assert_eq!("keep".parse::<StrategyKind>(), Ok(StrategyKind::Keep));
+        assert_eq!("set-free".parse::<Analysis>(), Ok(Analysis::SetFree));
+        assert_eq!("csv".parse::<Format>(), Ok(Format::Csv));
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn analyses_are_reproducible() {
+        let report = run_analysis(Analysis::Games(StrategyKind::Random), 30, 4, 3, |_, _| ());
+        assert_eq!(report.histograms.len(), 3);
+        assert_eq!(report.histograms[2].1.total(), 30);
+        assert_eq!(run_analysis(Analysis::Games(StrategyKind::Random), 30, 4, 1, |_, _| ()), report);
+        assert_eq!(run_analysis(Analysis::Caps, 30, 4, 2, |_, _| ()).histograms[0].1.total(), 30);
+    }
+
+    #[test]
+    fn no_samples() {
+        let json = run_analysis(Analysis::Games(StrategyKind::First), 0, 4, 2, |_, _| ()).render(Format::Json);
+        assert!(!json.contains("NaN"));
+        assert!(!json.contains("no_set_probability"));
+        assert!(json.contains("\"sets_on_board\": {\"total\": 0, \"mean\": null, \"counts\": []}"));
+        let table = run_analysis(Analysis::Caps, 0, 4, 2, |_, _| ()).render(Format::Table);
+        assert!(table.ends_with("\ncards_without_set: no samples\n"));
+    }
+}
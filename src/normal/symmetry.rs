@@ -0,0 +1,172 @@
+// Boards that are the same up to an affine map of F_3^4 (relabelling the values of an attribute, swapping attributes,
+// and more generally any bijection of the cards that takes sets to sets) play exactly the same. A card's index is its
+// vector in F_3^4, one base 3 digit per attribute
+use std::cmp::Ordering;
+
+use super::backend::{Card, CardMask, card_mask, third_index};
+
+// The third card of a set is -(a + b), and the third card for a and 0 is -a
+fn neg(a: usize) -> usize {
+    third_index(a, 0)
+}
+
+fn add(a: usize, b: usize) -> usize {
+    neg(third_index(a, b))
+}
+
+fn sub(a: usize, b: usize) -> usize {
+    add(a, neg(b))
+}
+
+// The canonical form of a board is the smallest (comparing the sorted card indices) of its images under maps that send
+// some p0, p1, ..., pd on the board to 0, e1, ..., ed, where the pi - p0 are a basis for the differences of the board.
+// Equivalent boards have the same such images, so they get the same canonical form.
+//
+// The points of the board in the span of p0, ..., pk go to exactly the indices below 3^k, and come first in the sorted
+// image, so a partial choice can be abandoned as soon as those compare worse than the best image found so far
+struct Canonicaliser {
+    mask: CardMask,
+    size: usize,
+    best: Option<Vec<usize>>,
+}
+
+impl Canonicaliser {
+    // `span[i]` is the card that is sent to index i
+    fn search(&mut self, span: &[usize]) {
+        // The image of the part of the board in the span so far
+        let prefix: Vec<usize> = (0..span.len()).filter(|&i| self.mask & (1 << span[i]) != 0).collect();
+        if let Some(best) = &self.best {
+            let shared = prefix.len().min(best.len());
+            match prefix[..shared].cmp(&best[..shared]) {
+                Ordering::Greater => return,
+                // Running out first means the next index is at least span.len(), which is bigger than anything in best
+                Ordering::Equal if prefix.len() < best.len() && best[prefix.len()] < span.len() => return,
+                _ => (),
+            }
+        }
+
+        if prefix.len() == self.size {
+            let better = match &self.best {
+                Some(best) => prefix < *best,
+                None => true,
+            };
+            if better {
+                self.best = Some(prefix);
+            }
+            return;
+        }
+
+        // Everything on the board that isn't in the span yet can be the next basis point
+        let in_span: CardMask = span.iter().fold(0, |m, &c| m | (1 << c));
+        let mut rest = self.mask & !in_span;
+        let mut extended = [0; 81];
+        extended[..span.len()].copy_from_slice(span);
+        while rest != 0 {
+            let next = rest.trailing_zeros() as usize;
+            rest &= rest - 1;
+            let direction = sub(next, span[0]);
+            for (i, &c) in span.iter().enumerate() {
+                extended[span.len() + i] = add(c, direction);
+                extended[2 * span.len() + i] = add(extended[span.len() + i], direction);
+            }
+            self.search(&extended[..3 * span.len()]);
+        }
+    }
+}
+
+// The indices of the canonical form, as a mask
+pub fn canonical_mask(cards: &[Card]) -> CardMask {
+    canonicalise(card_mask(cards))
+}
+
+// The canonical form of the cards in a mask
+pub fn canonicalise(mask: CardMask) -> CardMask {
+    let size = mask.count_ones() as usize;
+    let mut canonicaliser = Canonicaliser { mask, size, best: None };
+    for p0 in 0..81 {
+        if mask & (1 << p0) != 0 {
+            canonicaliser.search(&[p0]);
+        }
+    }
+    canonicaliser.best.unwrap_or_default().iter().fold(0, |m, &i| m | (1 << i))
+}
+
+// A representative of all boards equivalent to this one, in order of index. Duplicate cards are ignored
+pub fn canonical_form(cards: &[Card]) -> Vec<Card> {
+    let mask = canonical_mask(cards);
+    (0..81).filter(|i| mask & (1 << i) != 0).map(Card::from_index).collect()
+}
+
+// A hash of the canonical form that won't change between runs or versions (FNV-1a)
+pub fn canonical_hash(cards: &[Card]) -> u64 {
+    canonical_mask(cards).to_le_bytes().iter().fold(0xcbf29ce484222325, |hash, &byte| (hash ^ byte as u64).wrapping_mul(0x100000001b3))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use rand::{Rng, SeedableRng};
+    use rand::rngs::StdRng;
+    use super::super::backend::{find_sets_in, make_deck};
+    use super::super::generator::generate_board;
+
+    fn digits(i: usize) -> [usize; 4] {
+        [i % 3, i / 3 % 3, i / 9 % 3, i / 27]
+    }
+
+    // A random affine bijection, as the image of every card index
+    fn random_affine_map<R: Rng>(rng: &mut R) -> [usize; 81] {
+        loop {
+            let translation = rng.gen_range(0..81);
+            let columns: Vec<usize> = (0..4).map(|_| rng.gen_range(0..81)).collect();
+            let mut map = [0; 81];
+            for (i, image) in map.iter_mut().enumerate() {
+                *image = digits(i).iter().zip(&columns).fold(translation, |acc, (&d, &col)| {
+                    (0..d).fold(acc, |acc, _| add(acc, col))
+                });
+            }
+            if map.iter().collect::<HashSet<_>>().len() == 81 {
+                break map;
+            }
+        }
+    }
+
+    #[test]
+    fn equivalent_boards_match() {
+        let mut rng = StdRng::seed_from_u64(14);
+        for (cards, sets) in [(1, 0), (4, 0), (9, 12), (12, 3), (12, 0), (20, 0)] {
+            let board = generate_board(&mut rng, cards, sets).unwrap();
+            let form = canonical_form(&board);
+            assert_eq!(form.len(), cards);
+            assert_eq!(find_sets_in(&form).len(), sets);
+            assert_eq!(form[0].to_index(), 0);
+            for _ in 0..5 {
+                let map = random_affine_map(&mut rng);
+                let image: Vec<Card> = board.iter().map(|c| Card::from_index(map[c.to_index()])).collect();
+                assert_eq!(canonical_form(&image), form);
+                assert_eq!(canonical_hash(&image), canonical_hash(&board));
+            }
+        }
+    }
+
+    #[test]
+    fn counts_board_types() {
+        let deck = make_deck();
+        // Three cards are either a set or not
+        let triples: HashSet<u64> = (1..81).flat_map(|j| (j+1..81).map(move |k| (j, k)))
+            .map(|(j, k)| canonical_hash(&[deck[0], deck[j], deck[k]]))
+            .collect();
+        assert_eq!(triples.len(), 2);
+        // Every 20 card board without a set is the same up to symmetry
+        let mut rng = StdRng::seed_from_u64(20);
+        let caps: HashSet<CardMask> = (0..3).map(|_| canonical_mask(&generate_board(&mut rng, 20, 0).unwrap())).collect();
+        assert_eq!(caps.len(), 1);
+    }
+
+    #[test]
+    fn stable_hash() {
+        assert_eq!(canonical_hash(&[]), 0x88201fb960ff6465);
+        assert_eq!(canonical_form(&[Card::from_index(80)]), vec![Card::from_index(0)]);
+    }
+}
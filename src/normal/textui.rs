@@ -1,8 +1,12 @@
-use super::backend::{Card, Number, Shape, Shading, Colour as CardColour, Game, Move, find_sets, PlayError};
+use super::backend::{Card, Number, Shape, Shading, Colour as CardColour, Game, GameStatus, Hint, HintCosts, Move, Player, Rules, explain, has_set, PlayError, DrawError};
+use super::replay::{EventKind, Recorder, Replay};
+use super::puzzle::{Date, Guess, Puzzle};
 
+use std::fs;
 use std::io;
 use std::io::Write;
 
+use rand::{thread_rng, Rng};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 fn get_line() -> String {
@@ -15,7 +19,39 @@ fn get_line() -> String {
     }
 }
 
-pub fn game_loop() {
+const DEFAULT_SAVE_FILE: &str = "set.save";
+
+#[derive(Default)]
+pub struct GameOptions {
+    // Seed for the first game
+    pub seed: Option<u64>,
+    // Save file to resume the first game from
+    pub load: Option<String>,
+    // File to write a replay of each game to
+    pub record: Option<String>,
+    // Names of the players in a hot-seat game (a single player game if empty)
+    pub players: Vec<String>,
+    pub rules: Rules,
+    pub hint_costs: HintCosts,
+}
+
+pub fn game_loop(options: GameOptions) {
+    let mut seed = options.seed;
+    let mut loaded = match options.load {
+        None => None,
+        Some(file) => match fs::read_to_string(&file).map_err(|e| e.to_string())
+            .and_then(|s| Game::from_save_string(&s).map_err(|e| e.to_string())) {
+            Ok(game) => {
+                println!("Loaded game from {}", file);
+                Some(game)
+            }
+            Err(e) => {
+                println!("Couldn't load {}: {}", file, e);
+                return;
+            }
+        }
+    };
+
     print!("Welcome to set! Would you like to play a game? (y/n): ");
     io::stdout().flush().unwrap();
     'outer: loop {
@@ -36,54 +72,102 @@ pub fn game_loop() {
             }
         }
 
-        play_game();
+        // Only the first game uses a seed or save file given on the command line
+        let game = loaded.take().unwrap_or_else(|| {
+            let seed = seed.take().unwrap_or_else(|| thread_rng().gen());
+            println!("Seed: {}", seed);
+            let mut game = Game::with_seed(seed);
+            if !options.players.is_empty() {
+                game.set_players(options.players.clone());
+            }
+            game.set_rules(options.rules);
+            game.set_hint_costs(options.hint_costs);
+            game
+        });
+        play_game(game, options.record.as_deref());
 
         print!("That was fun! Would you like to play again? (y/n): ");
         io::stdout().flush().unwrap();
     }
 }
 
+// Draws and moves say which player made them
 enum Choice {
-    Draw3,
-    Cheat,
+    Draw3(usize),
+    Hint(usize),
     Quit,
     ToggleLetters,
-    Move(Move),
+    Save(String),
+    Undo,
+    Redo,
+    Move(usize, Move),
 }
 
+// The keys for the cards on screen, column by column (see the README)
+const LETTERS: [char; 21] = ['q', 'a', 'z', 'w', 's', 'x', 'e', 'd', 'c', 'r', 'f', 'v', 't', 'g', 'b', 'y', 'h', 'n', 'u', 'j', 'm'];
+
 fn letter_to_index(letter: char) -> Result<usize, ()> {
-    match letter.to_lowercase().next().unwrap() {
-        'q' => Ok(0),
-        'a' => Ok(1),
-        'z' => Ok(2),
-        'w' => Ok(3),
-        's' => Ok(4),
-        'x' => Ok(5),
-        'e' => Ok(6),
-        'd' => Ok(7),
-        'c' => Ok(8),
-        'r' => Ok(9),
-        'f' => Ok(10),
-        'v' => Ok(11),
-        't' => Ok(12),
-        'g' => Ok(13),
-        'b' => Ok(14),
-        'y' => Ok(15),
-        'h' => Ok(16),
-        'n' => Ok(17),
-        'u' => Ok(18),
-        'j' => Ok(19),
-        'm' => Ok(20),
-        _ => Err(()),
+    let letter = letter.to_lowercase().next().unwrap();
+    LETTERS.iter().position(|&l| l == letter).ok_or(())
+}
+
+fn index_to_letter(index: usize) -> Option<char> {
+    LETTERS.get(index).copied()
+}
+
+// Words have to be checked before letters, since e.g. "sav" is also a move
+fn parse_command(line: &str) -> Option<Choice> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.first() {
+        Some(&"save") => Some(Choice::Save(words.get(1).unwrap_or(&DEFAULT_SAVE_FILE).to_string())),
+        Some(&"undo") => Some(Choice::Undo),
+        Some(&"redo") => Some(Choice::Redo),
+        _ => None,
     }
 }
 
-fn get_move() -> Choice {
-    loop {
-        let ans = get_line();
+fn get_move(num_players: usize) -> Choice {
+    'outer: loop {
+        let line = get_line();
+
+        // File names can have colons in them, so commands are looked for before a player number
+        if let Some(choice) = parse_command(&line) {
+            break choice;
+        }
+
+        // Players buzz in by starting with their number, e.g. 1:qaz. There's no need to in a single player game
+        let (caller, ans) = match line.split_once(':') {
+            Some((p, rest)) => match p.trim().parse::<usize>() {
+                Ok(p) if p >= 1 && p <= num_players => (Some(p - 1), rest.trim_start().to_string()),
+                _ => {
+                    println!("There's no player {}! Players are numbered 1 to {}", p.trim(), num_players);
+                    continue;
+                }
+            },
+            None if num_players == 1 => (Some(0), line),
+            None => (None, line),
+        };
+        let need_caller = || println!("Who's calling? Start with your player number, e.g. 1:qaz");
+
+        if let Some(choice) = parse_command(&ans) {
+            break choice;
+        }
+
         match ans.to_ascii_lowercase().get(0..1) {
-            Some("p") => break Choice::Draw3,
-            Some(";") => break Choice::Cheat,
+            Some("p") => match caller {
+                Some(p) => break Choice::Draw3(p),
+                None => {
+                    need_caller();
+                    continue 'outer;
+                }
+            },
+            Some(";") => match caller {
+                Some(p) => break Choice::Hint(p),
+                None => {
+                    need_caller();
+                    continue 'outer;
+                }
+            },
             Some("k") => break Choice::Quit,
             Some("l") => break Choice::ToggleLetters,
             _ => (),
@@ -94,29 +178,42 @@ fn get_move() -> Choice {
                 if v.len() != 3 {
                     println!("Must specify 3 cards");
                 } else {
-                    break Choice::Move(Move(v[0], v[1], v[2]));
+                    match caller {
+                        Some(p) => break Choice::Move(p, Move(v[0], v[1], v[2])),
+                        None => need_caller(),
+                    }
                 }
             }
         }
     }
 }
 
-fn play_game() {
-    let mut game = Game::new();
+fn play_game(mut game: Game, record: Option<&str>) {
+    let mut recorder = Recorder::new(&game);
 
     // Main game loop
     loop {
         print_game(&game);
-        match get_move() {
-            Choice::Draw3 => {
-                if game.cards_in_play().len() == 21 {
-                    println!("Can't have more than 21 cards in play! (hint: there's guaranteed to be a set here)");
+        if let GameStatus::Finished { leftover, score } = game.status() {
+            print_summary(&game, &leftover, score);
+            break;
+        }
+        match get_move(game.players().len()) {
+            Choice::Draw3(player) => {
+                // Under official rules the backend deals with this (and charges for it)
+                if game.get_rules() == Rules::Casual && has_set(game.cards_in_play()) {
+                    println!("There's still a set on the board! Keep looking.");
                     continue;
                 }
-                match game.draw_3() {
+                match game.draw_3_as(player) {
                     Ok(()) => {
+                        recorder.record(EventKind::Draw3(player));
+                    }
+                    Err(DrawError::SetOnBoard) => {
+                        recorder.record(EventKind::Draw3(player));
+                        println!("There's a set on the board! That costs a point.");
                     }
-                    Err(()) => {
+                    Err(DrawError::DeckEmpty) => {
                         println!("There aren't enough cards in the deck. Game over!");
                         break;
                     }
@@ -129,29 +226,79 @@ fn play_game() {
             Choice::ToggleLetters => {
                 println!("Not implemented yet :(");
             }
-            Choice::Cheat => {
-                let sets = find_sets(&game);
-                if sets.len() == 0 {
-                    println!("There are no sets on the board. You have to draw 3 cards.");
-                } else {
-                    if sets.len() == 1 {
-                        println!("There is 1 set on the board:");
-                    } else {
-                        println!("There are {} sets on the board:", sets.len());
+            Choice::Undo => {
+                let was_ranked = game.is_ranked();
+                match game.undo() {
+                    Ok(()) => {
+                        recorder.record(EventKind::Undo);
+                        if was_ranked {
+                            println!("Undoing makes this a practice game, so it won't be ranked");
+                        }
                     }
-                    for s in sets.iter() {
-                        println!("{} {} {}", card_string(&game.cards_in_play()[s.0]), card_string(&game.cards_in_play()[s.1]), card_string(&game.cards_in_play()[s.2]));
+                    Err(()) => println!("Nothing to undo!"),
+                }
+            }
+            Choice::Redo => {
+                match game.redo() {
+                    Ok(()) => recorder.record(EventKind::Redo),
+                    Err(()) => println!("Nothing to redo!"),
+                }
+            }
+            Choice::Save(file) => {
+                match fs::write(&file, game.to_save_string()) {
+                    Ok(()) => println!("Saved game to {}", file),
+                    Err(e) => println!("Couldn't save to {}: {}", file, e),
+                }
+                continue;
+            }
+            Choice::Hint(player) => {
+                let score = game.players()[player].get_score();
+                let hint = game.hint_as(player);
+                recorder.record(EventKind::Hint(player));
+                let describe = |i: usize| {
+                    let card = card_string(&game.cards_in_play()[i]);
+                    match index_to_letter(i) {
+                        Some(letter) => format!("{} ({})", card, letter),
+                        None => card,
                     }
+                };
+                match hint {
+                    Hint::Count(0) => println!("There are no sets on the board. You have to draw 3 cards."),
+                    Hint::Count(1) => println!("There is 1 set on the board."),
+                    Hint::Count(n) => println!("There are {} sets on the board.", n),
+                    Hint::OneCard(c) => println!("{} is part of a set.", describe(c)),
+                    Hint::TwoCards(c1, c2) => println!("{} and {} are part of a set.", describe(c1), describe(c2)),
+                }
+                let cost = score - game.players()[player].get_score();
+                if cost != 0 {
+                    println!("That hint cost {} point{}.", cost, if cost == 1 { "" } else { "s" });
                 }
                 continue;
             }
-            Choice::Move(mve) => {
-                match game.attempt_move(&mve) {
+            Choice::Move(player, mve) => {
+                let auto_deals = game.auto_deals();
+                let board = game.cards_in_play().to_vec();
+                match game.attempt_move_as(player, &mve) {
                     Ok(()) => {
+                        recorder.record(EventKind::SetFound(player, mve));
+                        if game.auto_deals() > auto_deals {
+                            println!("There were no sets left on the board, so more cards were dealt.");
+                        }
                     }
                     Err(p) => {
                         match p {
-                            PlayError::NotASet => println!("Not a valid set! Try again!"),
+                            PlayError::NotASet => {
+                                recorder.record(EventKind::BadGuess(player, mve));
+                                if game.players().len() == 1 {
+                                    println!("Not a valid set! Try again!");
+                                } else {
+                                    println!("Not a valid set! {} loses a point", game.players()[player].get_name());
+                                }
+                                let Move(c1, c2, c3) = mve;
+                                for violation in explain(&board[c1], &board[c2], &board[c3]) {
+                                    println!("  {}", violation);
+                                }
+                            }
                             PlayError::InvalidMove => println!("Invalid move! Try again!"),
                         }
                     }
@@ -159,6 +306,152 @@ fn play_game() {
             }
         };
     }
+
+    if game.players().len() > 1 {
+        print_standings(&game);
+    }
+
+    if let Some(file) = record {
+        match fs::write(file, recorder.get_replay().to_replay_string()) {
+            Ok(()) => println!("Saved replay to {}", file),
+            Err(e) => println!("Couldn't save replay to {}: {}", file, e),
+        }
+    }
+}
+
+fn print_summary(game: &Game, leftover: &[Card], score: i32) {
+    println!("The deck is empty and there are no sets left. Game over!");
+    if game.players().len() == 1 {
+        println!("Final score: {}{}", score, if game.is_ranked() { "" } else { " (practice)" });
+    }
+    if leftover.is_empty() {
+        println!("You cleared the whole board!");
+    } else {
+        println!("{} cards left over: {}", leftover.len(), leftover.iter().map(card_string).collect::<Vec<String>>().join(" "));
+    }
+}
+
+fn print_standings(game: &Game) {
+    let mut players: Vec<&Player> = game.players().iter().collect();
+    players.sort_by_key(|p| -p.get_score());
+    let width = players.iter().map(|p| p.get_name().len()).max().unwrap_or(0).max("Player".len());
+
+    println!("Final standings:");
+    println!("     {:<width$}  Score", "Player");
+    let mut place = 0;
+    for (i, p) in players.iter().enumerate() {
+        // Tied players share a place
+        if i == 0 || p.get_score() != players[i - 1].get_score() {
+            place = i + 1;
+        }
+        println!("{:>3}. {:<width$}  {:>5}", place, p.get_name(), p.get_score());
+    }
+}
+
+// Steps through a recorded game one event at a time
+pub fn replay_loop(file: &str) {
+    let replay = match fs::read_to_string(file).map_err(|e| e.to_string())
+        .and_then(|s| Replay::from_replay_string(&s).map_err(|e| e.to_string())) {
+        Ok(replay) => replay,
+        Err(e) => {
+            println!("Couldn't load replay {}: {}", file, e);
+            return;
+        }
+    };
+
+    let boards = replay.boards();
+    let events = replay.get_events();
+    let mut step = 0;
+
+    loop {
+        print_game(&boards[step]);
+        if step == 0 {
+            println!("Start of game ({} events)", events.len());
+        } else {
+            let event = &events[step - 1];
+            println!("Event {}/{} at {:.1}s: {}", step, events.len(), event.time.as_secs_f64(), describe_event(&event.kind, &boards[step - 1]));
+        }
+        print!("Enter or n for next, b for back, k to quit: ");
+        io::stdout().flush().unwrap();
+
+        match get_line().trim().to_ascii_lowercase().as_str() {
+            "" | "n" => {
+                if step == events.len() {
+                    println!("That's the end of the game!");
+                } else {
+                    step += 1;
+                }
+            }
+            "b" => {
+                if step == 0 {
+                    println!("Already at the start!");
+                } else {
+                    step -= 1;
+                }
+            }
+            "k" => break,
+            _ => println!("Sorry, I don't understand. Please try again."),
+        }
+    }
+}
+
+pub fn puzzle_loop(date: Option<Date>, num_sets: usize) {
+    let date = date.unwrap_or_else(Date::today);
+    let mut puzzle = match Puzzle::for_date(date, num_sets) {
+        Ok(puzzle) => puzzle,
+        Err(e) => {
+            println!("{}", e);
+            return;
+        }
+    };
+
+    println!("Puzzle for {}: find all {} sets", date, num_sets);
+    while !puzzle.is_solved() {
+        println!("Found {} of {}", puzzle.get_found().len(), puzzle.num_sets());
+        println!();
+        print_board(puzzle.get_board());
+        match get_move(1) {
+            Choice::Move(_, mve) => match puzzle.guess(&mve) {
+                Ok(Guess::New) => println!("Found one!"),
+                Ok(Guess::AlreadyFound) => println!("You've already found that one"),
+                Err(PlayError::NotASet) => {
+                    println!("Not a valid set! Try again!");
+                    let Move(c1, c2, c3) = mve;
+                    let board = puzzle.get_board();
+                    for violation in explain(&board[c1], &board[c2], &board[c3]) {
+                        println!("  {}", violation);
+                    }
+                }
+                Err(PlayError::InvalidMove) => println!("Invalid move! Try again!"),
+            },
+            Choice::Quit => {
+                println!("Come back and finish it later!");
+                return;
+            }
+            _ => println!("You can only look for sets (or quit with k) in a puzzle"),
+        }
+    }
+    println!("You found all {} sets. See you tomorrow!", puzzle.num_sets());
+}
+
+// Describes an event, given the board it happened on
+fn describe_event(kind: &EventKind, before: &Game) -> String {
+    let name = |player: usize| before.players()[player].get_name().to_string();
+    let cards = |&Move(c1, c2, c3): &Move| {
+        [c1, c2, c3].iter()
+            .map(|&i| before.cards_in_play().get(i).map(card_string).unwrap_or_else(|| "?".to_string()))
+            .collect::<Vec<String>>()
+            .join(" ")
+    };
+    match kind {
+        EventKind::SetFound(player, mve) => format!("{} found the set {}", name(*player), cards(mve)),
+        EventKind::BadGuess(player, mve) => format!("{} guessed {}, which isn't a set", name(*player), cards(mve)),
+        EventKind::Draw3(player) if before.get_rules() == Rules::Official && has_set(before.cards_in_play()) => format!("{} asked for more cards when there was a set", name(*player)),
+        EventKind::Draw3(player) => format!("{} drew 3 cards", name(*player)),
+        EventKind::Hint(player) => format!("{} asked for a hint", name(*player)),
+        EventKind::Undo => "undid a move".to_string(),
+        EventKind::Redo => "redid a move".to_string(),
+    }
 }
 
 // - indicates a filled space
@@ -284,16 +577,27 @@ const WAVE_FULL: [&str; 11] =
 
 
 fn print_game(g: &Game) {
-    println!("Score: {}", g.get_score());
+    let practice = if g.is_ranked() { "" } else { " (practice)" };
+    if g.players().len() == 1 {
+        println!("Score: {}{}", g.get_score(), practice);
+    } else {
+        let scores: Vec<String> = g.players().iter().enumerate()
+            .map(|(i, p)| format!("{}. {}: {}", i + 1, p.get_name(), p.get_score()))
+            .collect();
+        println!("Scores: {}{}", scores.join("   "), practice);
+    }
     println!("Cards left in deck: {}", g.remaining_cards());
     println!();
+    print_board(g.cards_in_play());
+}
 
+fn print_board(cards: &[Card]) {
     // Print the cards in three equal lines
     let mut line1: Vec<Card> = Vec::new();
     let mut line2: Vec<Card> = Vec::new();
     let mut line3: Vec<Card> = Vec::new();
 
-    for (i, c) in g.cards_in_play().iter().enumerate() {
+    for (i, c) in cards.iter().enumerate() {
         match i % 3 {
             0 => {
                 line1.push(*c);
@@ -2,7 +2,8 @@ use std::collections::HashSet;
 use std::fmt;
 use std::error::Error;
 
-use rand::thread_rng;
+use rand::{thread_rng, Rng, SeedableRng};
+use rand::rngs::StdRng;
 use rand::seq::SliceRandom;
 
 pub struct Card {
@@ -61,8 +62,16 @@ pub struct Game {
 
 impl Game {
     pub fn new() -> Self {
+        Self::with_rng(thread_rng())
+    }
+
+    pub fn with_seed(seed: u64) -> Self {
+        Self::with_rng(StdRng::seed_from_u64(seed))
+    }
+
+    pub fn with_rng<R: Rng>(mut rng: R) -> Self {
         let mut out = Game {
-            deck: make_shuffled_deck(),
+            deck: make_shuffled_deck(&mut rng),
             in_play: vec![],
         };
         for _ in 0..7 {
@@ -146,11 +155,11 @@ fn make_deck() -> Vec<Card> {
     (1..64).map(Card::from_int).collect()
 }
 
-fn make_shuffled_deck() -> Vec<Card> {
+fn make_shuffled_deck<R: Rng + ?Sized>(rng: &mut R) -> Vec<Card> {
     let mut deck = make_deck();
 
     // Shuffle the deck
-    deck.shuffle(&mut thread_rng());
+    deck.shuffle(rng);
 
     deck
 }
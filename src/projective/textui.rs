@@ -3,6 +3,7 @@ use super::backend::{Move, CardPos, Game, PlayError, attempt_move, Card, MoveBui
 use std::io;
 use std::io::Write;
 
+use rand::{thread_rng, Rng};
 use termcolor::{Color, ColorChoice, ColorSpec, StandardStream, WriteColor};
 
 fn get_line() -> String {
@@ -15,7 +16,7 @@ fn get_line() -> String {
     }
 }
 
-pub fn game_loop() {
+pub fn game_loop(mut seed: Option<u64>) {
     print!("Welcome to projective set! Would you like to play a game? (y/n): ");
     io::stdout().flush().unwrap();
     'outer: loop {
@@ -36,7 +37,8 @@ pub fn game_loop() {
             }
         }
 
-        play_game();
+        // Only the first game uses a seed given on the command line
+        play_game(seed.take().unwrap_or_else(|| thread_rng().gen()));
 
         print!("That was fun! Would you like to play again? (y/n): ");
         io::stdout().flush().unwrap();
@@ -77,8 +79,9 @@ fn get_move() -> Choice {
     }
 }
 
-fn play_game() {
-    let mut game = Game::new();
+fn play_game(seed: u64) {
+    println!("Seed: {}", seed);
+    let mut game = Game::with_seed(seed);
 
     // Main game loop
     loop {